@@ -1,374 +1,521 @@
-use parsec::{State, SimpleError, Error, Parsec, Status, M};
+use parsec::{State, Error, ParseError, SimpleError, Parsec, Status, Reply, M};
 use std::fmt::{Debug, Display, Formatter};
 use std::fmt;
 use std::sync::Arc;
 use std::marker::PhantomData;
+use std::any::Any;
 
-#[derive(Debug, Clone)]
-pub struct One<T>{
+pub struct One<T, E=SimpleError>{
     input : PhantomData<T>,
+    error_type: PhantomData<E>,
 }
 
-impl<T> One <T> where T:Debug+Clone {
-    fn new() -> One<T> {
-        One{input:PhantomData}
+impl<T, E> One<T, E> where T:Debug+Clone {
+    pub fn new() -> One<T, E> {
+        One{input:PhantomData, error_type:PhantomData}
     }
 }
 
-impl<T> Parsec<T, T> for One<T> where T:Debug+Clone {
-    fn parse(&self, state:&mut State<T>)->Status<T>{
-        state.next().ok_or(SimpleError::new(state.pos(), String::from("eof")))
+impl<T, E> Parsec<T, T, E> for One<T, E> where T:Debug+Clone, E:Error+From<ParseError> {
+    fn parse(&self, state:&mut State<T>)->Status<T, E>{
+        match state.next() {
+            Some(x) => Ok(x),
+            None => {
+                let err = state.eof_error();
+                Err(E::from(state.failure(err)))
+            },
+        }
+    }
+    fn reply(&self, state:&mut State<T>)->Reply<T, E>{
+        Reply::token(self.parse(state))
     }
 }
 
-impl<'a, T> FnOnce<(&'a mut State<T>, )> for One<T> where T:Debug+Clone {
-    type Output = Status<T>;
-    extern "rust-call" fn call_once(self, _: (&'a mut State<T>, )) -> Status<T> {
+impl<'a, T, E> FnOnce<(&'a mut State<T>, )> for One<T, E> where T:Debug+Clone, E:Error+From<ParseError> {
+    type Output = Status<T, E>;
+    extern "rust-call" fn call_once(self, _: (&'a mut State<T>, )) -> Status<T, E> {
         panic!("Not implement!");
     }
 }
 
-impl<'a, T> FnMut<(&'a mut State<T>, )> for One<T> where T:Debug+Clone {
-    extern "rust-call" fn call_mut(&mut self, _: (&'a mut State<T>, )) -> Status<T> {
+impl<'a, T, E> FnMut<(&'a mut State<T>, )> for One<T, E> where T:Debug+Clone, E:Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'a mut State<T>, )) -> Status<T, E> {
         panic!("Not implement!");
     }
 }
 
-impl<'a, T> Fn<(&'a mut State<T>, )> for One<T> where T:Debug+Clone {
-    extern "rust-call" fn call(&self, args: (&'a mut State<T>, )) -> Status<T> {
+impl<'a, T, E> Fn<(&'a mut State<T>, )> for One<T, E> where T:Debug+Clone, E:Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'a mut State<T>, )) -> Status<T, E> {
         let (state, ) = args;
         self.parse(state)
     }
 }
 
-impl<T:'static+Debug+Clone> M<T, T> for One<T>{}
+impl<T, E> Clone for One<T, E> {
+    fn clone(&self)->Self {
+        One{input:PhantomData, error_type:PhantomData}
+    }
+
+    fn clone_from(&mut self, _: &Self) {
+    }
+}
 
+impl<T, E> Debug for One<T, E> {
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        write!(formatter, "<one parsec>")
+    }
+}
+
+impl<'a, T:'a+Debug+Clone, E:'static+Error+From<ParseError>> M<'a, T, T, E> for One<T, E>{}
+
+// The constructors make parsers failing with SimpleError, the default error type. Their _with
+// twins are generic over the error type, as eq_with::<_, ParseError>('a').
 pub fn one<T>() -> One<T> where T:Debug+Clone {
     One::new()
 }
 
-#[derive(Debug, Clone)]
-pub struct Equal<T>{
+pub fn one_with<T, E>() -> One<T, E> where T:Debug+Clone {
+    One::new()
+}
+
+pub struct Equal<T, E=SimpleError>{
     element : T,
+    error_type: PhantomData<E>,
 }
 
-impl<T> Equal<T> where T:Eq+Display+Debug+Clone {
-    fn new(element:T) -> Equal<T> {
-        Equal{element:element}
+impl<T, E> Equal<T, E> where T:Eq+Display+Debug+Clone {
+    pub fn new(element:T) -> Equal<T, E> {
+        Equal{element:element, error_type:PhantomData}
     }
 }
 
-impl<T> Parsec<T, T> for Equal<T> where T:Eq+Display+Debug+Clone {
-    fn parse(&self, state:&mut State<T>)->Status<T>{
-        let ref value = self.element;
-        let val = state.next_by(&|val:&T|val.eq(value));
-        val.map_err(
-                |_:SimpleError|{
-                    let pos = state.pos();
-                    let element = self.element.clone();
-                    let message = format!("expect {} at {} but missmatch", element, pos);
-                    SimpleError::new(pos, message)
-                })
+impl<T, E> Parsec<T, T, E> for Equal<T, E> where T:Eq+Display+Debug+Clone, E:Error+From<ParseError> {
+    fn parse(&self, state:&mut State<T>)->Status<T, E>{
+        match state.peek() {
+            Some(item) => if item == self.element {
+                state.next();
+                Ok(item)
+            } else {
+                let err = ParseError::unexpected_in(state.span(), format!("{:?}", item));
+                Err(E::from(state.failure(err.expect(format!("{:?}", self.element)))))
+            },
+            None => Err(E::from(state.failure(state.eof_error().expect(format!("{:?}", self.element))))),
+        }
+    }
+    fn reply(&self, state:&mut State<T>)->Reply<T, E>{
+        Reply::token(self.parse(state))
     }
 }
 
-impl<'a, T> FnOnce<(&'a mut State<T>, )> for Equal<T> where T:Eq+Display+Debug+Clone {
-    type Output = Status<T>;
-    extern "rust-call" fn call_once(self, _: (&'a mut State<T>, )) -> Status<T> {
+impl<'a, T, E> FnOnce<(&'a mut State<T>, )> for Equal<T, E>
+where T:Eq+Display+Debug+Clone, E:Error+From<ParseError> {
+    type Output = Status<T, E>;
+    extern "rust-call" fn call_once(self, _: (&'a mut State<T>, )) -> Status<T, E> {
         panic!("Not implement!");
     }
 }
 
-impl<'a, T> FnMut<(&'a mut State<T>, )> for Equal<T> where T:Eq+Display+Debug+Clone {
-    extern "rust-call" fn call_mut(&mut self, _: (&'a mut State<T>, )) -> Status<T> {
+impl<'a, T, E> FnMut<(&'a mut State<T>, )> for Equal<T, E>
+where T:Eq+Display+Debug+Clone, E:Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'a mut State<T>, )) -> Status<T, E> {
         panic!("Not implement!");
     }
 }
 
-impl<'a, T> Fn<(&'a mut State<T>, )> for Equal<T> where T:Eq+Display+Debug+Clone {
-    extern "rust-call" fn call(&self, args: (&'a mut State<T>, )) -> Status<T> {
+impl<'a, T, E> Fn<(&'a mut State<T>, )> for Equal<T, E>
+where T:Eq+Display+Debug+Clone, E:Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'a mut State<T>, )) -> Status<T, E> {
         let (state, ) = args;
         self.parse(state)
     }
 }
 
-impl<T:'static+Eq+Display+Debug+Clone> M<T, T> for Equal<T>{}
+impl<T, E> Clone for Equal<T, E> where T:Clone {
+    fn clone(&self)->Self {
+        Equal{element:self.element.clone(), error_type:PhantomData}
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.element = source.element.clone();
+    }
+}
+
+impl<T, E> Debug for Equal<T, E> where T:Debug {
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        write!(formatter, "<equal parsec({:?})>", self.element)
+    }
+}
+
+impl<'a, T:'a+Eq+Display+Debug+Clone, E:'static+Error+From<ParseError>> M<'a, T, T, E> for Equal<T, E>{}
 
 pub fn eq<T>(element:T) -> Equal<T> where T:Eq+Display+Debug+Clone {
     Equal::new(element)
 }
 
-#[derive(Debug, Clone)]
-pub struct NotEqual<T>{
+pub fn eq_with<T, E>(element:T) -> Equal<T, E> where T:Eq+Display+Debug+Clone {
+    Equal::new(element)
+}
+
+pub struct NotEqual<T, E=SimpleError>{
     element : T,
+    error_type: PhantomData<E>,
 }
 
-impl<T> NotEqual<T> where T:Eq+Display+Debug+Clone {
-    fn new(element:T) -> NotEqual<T> {
-        NotEqual{element:element}
+impl<T, E> NotEqual<T, E> where T:Eq+Display+Debug+Clone {
+    pub fn new(element:T) -> NotEqual<T, E> {
+        NotEqual{element:element, error_type:PhantomData}
     }
 }
 
-impl<T> Parsec<T, T> for NotEqual<T> where T:Eq+Display+Debug+Clone {
-    fn parse(&self, state:&mut State<T>)->Status<T>{
-        let ref value = self.element;
-        let val = state.next_by(&|val:&T|val.ne(value));
-        val.map_err(
-                |_:SimpleError|{
-                    let pos = state.pos();
-                    let element = self.element.clone();
-                    let message = format!("expect {} not equal element at {}", element, pos);
-                    SimpleError::new(pos, message)
-                })
+impl<T, E> Parsec<T, T, E> for NotEqual<T, E> where T:Eq+Display+Debug+Clone, E:Error+From<ParseError> {
+    fn parse(&self, state:&mut State<T>)->Status<T, E>{
+        match state.peek() {
+            Some(item) => if item != self.element {
+                state.next();
+                Ok(item)
+            } else {
+                let err = ParseError::unexpected_in(state.span(), format!("{:?}", item));
+                Err(E::from(state.failure(err.expect(format!("not {:?}", self.element)))))
+            },
+            None => Err(E::from(state.failure(state.eof_error().expect(format!("not {:?}", self.element))))),
+        }
+    }
+    fn reply(&self, state:&mut State<T>)->Reply<T, E>{
+        Reply::token(self.parse(state))
     }
 }
 
-impl<'a, T> FnOnce<(&'a mut State<T>, )> for NotEqual<T> where T:Eq+Display+Debug+Clone {
-    type Output = Status<T>;
-    extern "rust-call" fn call_once(self, _: (&'a mut State<T>, )) -> Status<T> {
+impl<'a, T, E> FnOnce<(&'a mut State<T>, )> for NotEqual<T, E>
+where T:Eq+Display+Debug+Clone, E:Error+From<ParseError> {
+    type Output = Status<T, E>;
+    extern "rust-call" fn call_once(self, _: (&'a mut State<T>, )) -> Status<T, E> {
         panic!("Not implement!");
     }
 }
 
-impl<'a, T> FnMut<(&'a mut State<T>, )> for NotEqual<T> where T:Eq+Display+Debug+Clone {
-    extern "rust-call" fn call_mut(&mut self, _: (&'a mut State<T>, )) -> Status<T> {
+impl<'a, T, E> FnMut<(&'a mut State<T>, )> for NotEqual<T, E>
+where T:Eq+Display+Debug+Clone, E:Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'a mut State<T>, )) -> Status<T, E> {
         panic!("Not implement!");
     }
 }
 
-impl<'a, T> Fn<(&'a mut State<T>, )> for NotEqual<T> where T:Eq+Display+Debug+Clone {
-    extern "rust-call" fn call(&self, args: (&'a mut State<T>, )) -> Status<T> {
+impl<'a, T, E> Fn<(&'a mut State<T>, )> for NotEqual<T, E>
+where T:Eq+Display+Debug+Clone, E:Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'a mut State<T>, )) -> Status<T, E> {
         let (state, ) = args;
         self.parse(state)
     }
 }
 
-impl<T:'static+Eq+Display+Debug+Clone> M<T, T> for NotEqual<T>{}
+impl<T, E> Clone for NotEqual<T, E> where T:Clone {
+    fn clone(&self)->Self {
+        NotEqual{element:self.element.clone(), error_type:PhantomData}
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.element = source.element.clone();
+    }
+}
+
+impl<T, E> Debug for NotEqual<T, E> where T:Debug {
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        write!(formatter, "<not equal parsec({:?})>", self.element)
+    }
+}
+
+impl<'a, T:'a+Eq+Display+Debug+Clone, E:'static+Error+From<ParseError>> M<'a, T, T, E> for NotEqual<T, E>{}
 
 pub fn ne<T>(element:T) -> NotEqual<T> where T:Eq+Display+Debug+Clone {
     NotEqual::new(element)
 }
 
-pub struct Eof<T>{
+pub fn ne_with<T, E>(element:T) -> NotEqual<T, E> where T:Eq+Display+Debug+Clone {
+    NotEqual::new(element)
+}
+
+pub struct Eof<T, E=SimpleError>{
     data: PhantomData<T>,
+    error_type: PhantomData<E>,
 }
 
-impl<T> Eof<T>{
-    fn new() -> Eof<T> {
-        Eof{data:PhantomData}
+impl<T, E> Eof<T, E>{
+    pub fn new() -> Eof<T, E> {
+        Eof{data:PhantomData, error_type:PhantomData}
     }
 }
 
-impl<T> Parsec<T, ()> for Eof<T> where T:Clone+Display {
-    fn parse(&self, state:&mut State<T>)->Status<()>{
-        let val = state.next();
-        if val.is_none() {
-            Ok(())
-        } else {
-            let pos = state.pos();
-            let message = format!("expect eof at {} but got value {}", pos, val.unwrap());
-            Err(SimpleError::new(pos, message))
+impl<T, E> Parsec<T, (), E> for Eof<T, E> where T:Clone+Debug, E:Error+From<ParseError> {
+    fn parse(&self, state:&mut State<T>)->Status<(), E>{
+        match state.peek() {
+            None => match state.input_error() {
+                Some(err) => Err(E::from(state.failure(err))),
+                None => Ok(()),
+            },
+            Some(item) => {
+                let err = ParseError::unexpected_in(state.span(), format!("{:?}", item));
+                Err(E::from(state.failure(err.expect(String::from("end of input")))))
+            }
         }
     }
+    fn reply(&self, state:&mut State<T>)->Reply<(), E>{
+        Reply::Empty(self.parse(state))
+    }
 }
 
-impl<'a, S, T> FnOnce<(&'a mut S, )> for Eof<T> where S:State<T>{
-    type Output = Status<()>;
-    extern "rust-call" fn call_once(self, _: (&'a mut S, )) -> Status<()> {
+impl<'a, S, T, E> FnOnce<(&'a mut S, )> for Eof<T, E> where S:State<T>, E:Error+From<ParseError> {
+    type Output = Status<(), E>;
+    extern "rust-call" fn call_once(self, _: (&'a mut S, )) -> Status<(), E> {
         panic!("Not implement!");
     }
 }
 
-impl<'a, S, T> FnMut<(&'a mut S, )> for Eof<T> where S:State<T>{
-    extern "rust-call" fn call_mut(&mut self, _: (&'a mut S, )) -> Status<()> {
+impl<'a, S, T, E> FnMut<(&'a mut S, )> for Eof<T, E> where S:State<T>, E:Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'a mut S, )) -> Status<(), E> {
         panic!("Not implement!");
     }
 }
 
-impl<'a, S, T> Fn<(&'a mut S, )> for Eof<T> where T:Clone+Display, S:State<T> {
-    extern "rust-call" fn call(&self, args: (&'a mut S, )) -> Status<()> {
+impl<'a, S, T, E> Fn<(&'a mut S, )> for Eof<T, E>
+where T:Clone+Debug, S:State<T>, E:Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'a mut S, )) -> Status<(), E> {
         let (state, ) = args;
         self.parse(state)
     }
 }
 
-impl<T> Clone for Eof<T> where T:Clone {
+impl<T, E> Clone for Eof<T, E> where T:Clone {
     fn clone(&self)->Self {
-        eof::<T>()
+        Eof::new()
     }
 
     fn clone_from(&mut self, _: &Self) {
     }
 }
 
-impl<T> Debug for Eof<T> where T:Clone{
+impl<T, E> Debug for Eof<T, E> where T:Clone{
     fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
         write!(formatter, "<eof parsec>")
     }
 }
 
-impl<T:'static+Debug+Display+Clone> M<T, ()> for Eof<T>{}
+impl<'a, T:'a+Debug+Clone, E:'static+Error+From<ParseError>> M<'a, T, (), E> for Eof<T, E>{}
 
 pub fn eof<T>() -> Eof<T> {
     Eof::new()
 }
 
-#[derive(Debug, Clone)]
-pub struct OneOf<T> {
+pub fn eof_with<T, E>() -> Eof<T, E> {
+    Eof::new()
+}
+
+pub struct OneOf<T, E=SimpleError> {
     elements: Vec<T>,
+    error_type: PhantomData<E>,
 }
 
-impl<T> OneOf<T> where T:Eq+Display+Clone+Debug {
-    pub fn new(elements:&Vec<T>) -> OneOf<T> {
+impl<T, E> OneOf<T, E> where T:Eq+Display+Clone+Debug {
+    pub fn new(elements:&Vec<T>)->OneOf<T, E> {
         let mut es = Vec::new();
         es.push_all(&elements);
-        OneOf{elements:es}
+        OneOf{elements:es, error_type:PhantomData}
     }
 }
 
-impl<T> Parsec<T, T> for OneOf<T> where T:Eq+Display+Clone+Debug {
-    fn parse(&self, state:&mut State<T>)->Status<T>{
-        let next = state.next();
-        if next.is_none() {
-            Err(SimpleError::new(state.pos(), String::from("eof")))
-        } else {
-            let it = next.unwrap();
-            for d in self.elements.iter() {
-                if d == &it {
+impl<T, E> Parsec<T, T, E> for OneOf<T, E> where T:Eq+Display+Clone+Debug, E:Error+From<ParseError> {
+    fn parse(&self, state:&mut State<T>)->Status<T, E>{
+        let err = match state.peek() {
+            None => state.eof_error(),
+            Some(it) => {
+                if self.elements.contains(&it) {
+                    state.next();
                     return Ok(it);
                 }
+                ParseError::unexpected_in(state.span(), format!("{:?}", it))
             }
-            let message = format!("<expect one of {:?}, got:{}>", self.elements, it);
-            Err(SimpleError::new(state.pos(), String::from(message)))
-        }
+        };
+        Err(E::from(state.failure(self.elements.iter().fold(err, |err, d| err.expect(format!("{:?}", d))))))
+    }
+    fn reply(&self, state:&mut State<T>)->Reply<T, E>{
+        Reply::token(self.parse(state))
     }
 }
 
-impl<'a, T> FnOnce<(&'a mut State<T>, )> for OneOf<T> {
-    type Output = Status<T>;
-    extern "rust-call" fn call_once(self, _: (&'a mut State<T>, )) -> Status<T> {
+impl<'a, T, E> FnOnce<(&'a mut State<T>, )> for OneOf<T, E> where E:Error+From<ParseError> {
+    type Output = Status<T, E>;
+    extern "rust-call" fn call_once(self, _: (&'a mut State<T>, )) -> Status<T, E> {
         panic!("Not implement!");
     }
 }
 
-impl<'a, T> FnMut<(&'a mut State<T>, )> for OneOf<T> {
-    extern "rust-call" fn call_mut(&mut self, _: (&'a mut State<T>, )) -> Status<T> {
+impl<'a, T, E> FnMut<(&'a mut State<T>, )> for OneOf<T, E> where E:Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'a mut State<T>, )) -> Status<T, E> {
         panic!("Not implement!");
     }
 }
 
-impl<'a, T> Fn<(&'a mut State<T>, )> for OneOf<T> where T:Eq+Clone+Display+Debug {
-    extern "rust-call" fn call(&self, args: (&'a mut State<T>, )) -> Status<T> {
+impl<'a, T, E> Fn<(&'a mut State<T>, )> for OneOf<T, E>
+where T:Eq+Clone+Display+Debug, E:Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'a mut State<T>, )) -> Status<T, E> {
         let (state, ) = args;
         self.parse(state)
     }
 }
 
-impl<T:'static+Eq+Debug+Display+Clone> M<T, T> for OneOf<T>{}
+impl<T, E> Clone for OneOf<T, E> where T:Clone {
+    fn clone(&self)->Self {
+        OneOf{elements:self.elements.clone(), error_type:PhantomData}
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.elements = source.elements.clone();
+    }
+}
+
+impl<T, E> Debug for OneOf<T, E> where T:Debug {
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        write!(formatter, "<one of parsec({:?})>", self.elements)
+    }
+}
+
+impl<'a, T:'a+Eq+Debug+Display+Clone, E:'static+Error+From<ParseError>> M<'a, T, T, E> for OneOf<T, E>{}
 
 pub fn one_of<T:'static+Eq+Debug+Display>(elements:&Vec<T>)->OneOf<T>
         where T:Eq+Display+Clone+Debug {
     OneOf::new(&elements)
 }
 
-#[derive(Debug, Clone)]
-pub struct NoneOf<T> {
+pub fn one_of_with<T:'static+Eq+Debug+Display, E>(elements:&Vec<T>)->OneOf<T, E>
+        where T:Eq+Display+Clone+Debug {
+    OneOf::new(&elements)
+}
+
+pub struct NoneOf<T, E=SimpleError> {
     elements: Vec<T>,
+    error_type: PhantomData<E>,
 }
 
-impl<T> NoneOf<T> where T:Eq+Display+Clone+Debug {
-    pub fn new(elements:&Vec<T>) -> NoneOf<T> {
+impl<T, E> NoneOf<T, E> where T:Eq+Display+Clone+Debug {
+    pub fn new(elements:&Vec<T>)->NoneOf<T, E> {
         let mut es = Vec::new();
         es.push_all(&elements);
-        NoneOf{elements:es}
+        NoneOf{elements:es, error_type:PhantomData}
     }
 }
 
-impl<T> Parsec<T, T> for NoneOf<T> where T:Eq+Display+Clone+Debug {
-    fn parse(&self, state:&mut State<T>)->Status<T>{
-        let next = state.next();
-        if next.is_none() {
-            Err(SimpleError::new(state.pos(), String::from("eof")))
-        } else {
-            let it = next.unwrap();
-            for d in self.elements.iter() {
-                if d == &it {
-                    let message = format!("<expect none of {:?}, got:{}>", self.elements, it);
-                    return Err(SimpleError::new(state.pos(), String::from(message)))
-                }
-            }
-            return Ok(it);
+impl<T, E> Parsec<T, T, E> for NoneOf<T, E> where T:Eq+Display+Clone+Debug, E:Error+From<ParseError> {
+    fn parse(&self, state:&mut State<T>)->Status<T, E>{
+        match state.peek() {
+            None => Err(E::from(state.failure(state.eof_error().expect(format!("none of {:?}", self.elements))))),
+            Some(it) => if self.elements.contains(&it) {
+                let err = ParseError::unexpected_in(state.span(), format!("{:?}", it));
+                Err(E::from(state.failure(err.expect(format!("none of {:?}", self.elements)))))
+            } else {
+                state.next();
+                Ok(it)
+            },
         }
     }
+    fn reply(&self, state:&mut State<T>)->Reply<T, E>{
+        Reply::token(self.parse(state))
+    }
 }
 
-impl<'a, T> FnOnce<(&'a mut State<T>, )> for NoneOf<T> {
-    type Output = Status<T>;
-    extern "rust-call" fn call_once(self, _: (&'a mut State<T>, )) -> Status<T> {
+impl<'a, T, E> FnOnce<(&'a mut State<T>, )> for NoneOf<T, E> where E:Error+From<ParseError> {
+    type Output = Status<T, E>;
+    extern "rust-call" fn call_once(self, _: (&'a mut State<T>, )) -> Status<T, E> {
         panic!("Not implement!");
     }
 }
 
-impl<'a, T> FnMut<(&'a mut State<T>, )> for NoneOf<T> {
-    extern "rust-call" fn call_mut(&mut self, _: (&'a mut State<T>, )) -> Status<T> {
+impl<'a, T, E> FnMut<(&'a mut State<T>, )> for NoneOf<T, E> where E:Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'a mut State<T>, )) -> Status<T, E> {
         panic!("Not implement!");
     }
 }
 
-impl<'a, T> Fn<(&'a mut State<T>, )> for NoneOf<T> where T:Eq+Clone+Display+Debug {
-    extern "rust-call" fn call(&self, args: (&'a mut State<T>, )) -> Status<T> {
+impl<'a, T, E> Fn<(&'a mut State<T>, )> for NoneOf<T, E>
+where T:Eq+Clone+Display+Debug, E:Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'a mut State<T>, )) -> Status<T, E> {
         let (state, ) = args;
         self.parse(state)
     }
 }
 
-impl<T:'static+Eq+Debug+Display+Clone> M<T, T> for NoneOf<T>{}
+impl<T, E> Clone for NoneOf<T, E> where T:Clone {
+    fn clone(&self)->Self {
+        NoneOf{elements:self.elements.clone(), error_type:PhantomData}
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.elements = source.elements.clone();
+    }
+}
+
+impl<T, E> Debug for NoneOf<T, E> where T:Debug {
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        write!(formatter, "<none of parsec({:?})>", self.elements)
+    }
+}
+
+impl<'a, T:'a+Eq+Debug+Display+Clone, E:'static+Error+From<ParseError>> M<'a, T, T, E> for NoneOf<T, E>{}
 
 pub fn none_of<T:'static+Eq+Debug+Display>(elements:&Vec<T>)->NoneOf<T>
         where T:Eq+Display+Clone+Debug {
     NoneOf::new(&elements)
 }
 
-pub struct Pack<I, T>{
+pub fn none_of_with<T:'static+Eq+Debug+Display, E>(elements:&Vec<T>)->NoneOf<T, E>
+        where T:Eq+Display+Clone+Debug {
+    NoneOf::new(&elements)
+}
+
+pub struct Pack<I, T, E=SimpleError>{
     element : T,
     input_type: PhantomData<I>,
+    error_type: PhantomData<E>,
 }
 
-impl<I, T> Pack<I, T> where T:Clone+Debug {
-    fn new(element:T) -> Pack<I, T> {
-        Pack{element:element, input_type:PhantomData}
+impl<I, T, E> Pack<I, T, E> where T:Clone+Debug {
+    pub fn new(element:T) -> Pack<I, T, E> {
+        Pack{element:element, input_type:PhantomData, error_type:PhantomData}
     }
 }
 
-impl<I, T> Parsec<I, T> for Pack<I, T> where T:Clone+Debug {
-    fn parse(&self, _:&mut State<I>)->Status<T> {
+impl<I, T, E> Parsec<I, T, E> for Pack<I, T, E> where T:Clone+Debug, E:Error+From<ParseError> {
+    fn parse(&self, _:&mut State<I>)->Status<T, E> {
         Ok(self.element.clone())
     }
+    fn reply(&self, state:&mut State<I>)->Reply<T, E>{
+        Reply::Empty(self.parse(state))
+    }
 }
 
-impl<'a, I, T> FnOnce<(&'a mut State<I>, )> for Pack<I, T> where T:Clone+Debug {
-    type Output = Status<T>;
-    extern "rust-call" fn call_once(self, _: (&'a mut State<I>, )) -> Status<T> {
+impl<'a, I, T, E> FnOnce<(&'a mut State<I>, )> for Pack<I, T, E>
+where T:Clone+Debug, E:Error+From<ParseError> {
+    type Output = Status<T, E>;
+    extern "rust-call" fn call_once(self, _: (&'a mut State<I>, )) -> Status<T, E> {
         panic!("Not implement!");
     }
 }
 
-impl<'a, I, T> FnMut<(&'a mut State<I>, )> for Pack<I, T> where T:Clone+Debug {
-    extern "rust-call" fn call_mut(&mut self, _: (&'a mut State<I>, )) -> Status<T> {
+impl<'a, I, T, E> FnMut<(&'a mut State<I>, )> for Pack<I, T, E>
+where T:Clone+Debug, E:Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'a mut State<I>, )) -> Status<T, E> {
         panic!("Not implement!");
     }
 }
 
-impl<'a, I, T> Fn<(&'a mut State<I>, )> for Pack<I, T> where T:Clone+Debug {
-    extern "rust-call" fn call(&self, args: (&'a mut State<I>, )) -> Status<T> {
+impl<'a, I, T, E> Fn<(&'a mut State<I>, )> for Pack<I, T, E> where T:Clone+Debug, E:Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'a mut State<I>, )) -> Status<T, E> {
         let (state, ) = args;
         self.parse(state)
     }
 }
 
-impl<I, T> Clone for Pack<I, T> where T:Clone+Debug {
+impl<I, T, E> Clone for Pack<I, T, E> where T:Clone+Debug {
     fn clone(&self)->Self {
-        Pack{element:self.element.clone(), input_type:PhantomData}
+        Pack{element:self.element.clone(), input_type:PhantomData, error_type:PhantomData}
     }
 
     fn clone_from(&mut self, source: &Self) {
@@ -376,60 +523,71 @@ impl<I, T> Clone for Pack<I, T> where T:Clone+Debug {
     }
 }
 
-impl<I, T> Debug for Pack<I, T> where T:Clone+Debug {
+impl<I, T, E> Debug for Pack<I, T, E> where T:Clone+Debug {
     fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
         write!(formatter, "<pack parsec({:?})>", self.element)
     }
 }
 
-impl<I:'static+Clone, T:'static+Debug+Clone> M<I, T> for Pack<I, T>{}
+impl<'a, I:'a+Clone, T:'a+Debug+Clone, E:'static+Error+From<ParseError>> M<'a, I, T, E> for Pack<I, T, E>{}
 
 pub fn pack<I, T>(element:T) -> Pack<I, T> where T:Clone+Debug {
     Pack::new(element)
 }
 
-pub struct Fail<T, R>{
+pub fn pack_with<I, T, E>(element:T) -> Pack<I, T, E> where T:Clone+Debug {
+    Pack::new(element)
+}
+
+pub struct Fail<T, R, E=SimpleError>{
     message:Arc<String>,
     input_type: PhantomData<T>,
     output_type: PhantomData<R>,
+    error_type: PhantomData<E>,
 }
 
-impl<T, R> Fail<T, R> where T: Clone, R:Clone {
-    fn new(message:String) -> Fail<T, R> {
+impl<T, R, E> Fail<T, R, E> where T: Clone, R:Clone {
+    pub fn new(message:String) -> Fail<T, R, E> {
         let msg = Arc::new(message);
-        Fail{message:msg, input_type:PhantomData, output_type:PhantomData}
+        Fail{message:msg, input_type:PhantomData, output_type:PhantomData, error_type:PhantomData}
     }
 }
 
-impl<T, R> Parsec<T, R> for Fail<T, R> where T:Clone, R: Clone {
-    fn parse(&self, state:&mut State<T>)->Status<R>{
-        Err(SimpleError::new(state.pos(), String::from(self.message.as_str())))
+impl<T, R, E> Parsec<T, R, E> for Fail<T, R, E> where T:Clone, R: Clone, E:Error+From<ParseError> {
+    fn parse(&self, state:&mut State<T>)->Status<R, E>{
+        Err(E::from(state.failure(ParseError::at(state.source_pos(), String::from(self.message.as_str())))))
+    }
+    fn reply(&self, state:&mut State<T>)->Reply<R, E>{
+        Reply::Empty(self.parse(state))
     }
 }
 
-impl<'a, T, R> FnOnce<(&'a mut State<T>, )> for Fail<T, R> where T:Clone, R:Clone {
-    type Output = Status<R>;
-    extern "rust-call" fn call_once(self, _: (&'a mut State<T>, )) -> Status<R> {
+impl<'a, T, R, E> FnOnce<(&'a mut State<T>, )> for Fail<T, R, E>
+where T:Clone, R:Clone, E:Error+From<ParseError> {
+    type Output = Status<R, E>;
+    extern "rust-call" fn call_once(self, _: (&'a mut State<T>, )) -> Status<R, E> {
         panic!("Not implement!");
     }
 }
 
-impl<'a, T, R> FnMut<(&'a mut State<T>, )> for Fail<T, R> where T:Clone, R:Clone {
-    extern "rust-call" fn call_mut(&mut self, _: (&'a mut State<T>, )) -> Status<R> {
+impl<'a, T, R, E> FnMut<(&'a mut State<T>, )> for Fail<T, R, E>
+where T:Clone, R:Clone, E:Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'a mut State<T>, )) -> Status<R, E> {
         panic!("Not implement!");
     }
 }
 
-impl<'a, T, R> Fn<(&'a mut State<T>, )> for Fail<T, R> where T:Clone, R:Clone {
-    extern "rust-call" fn call(&self, args: (&'a mut State<T>, )) -> Status<R> {
+impl<'a, T, R, E> Fn<(&'a mut State<T>, )> for Fail<T, R, E>
+where T:Clone, R:Clone, E:Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'a mut State<T>, )) -> Status<R, E> {
         let (state, ) = args;
         self.parse(state)
     }
 }
 
-impl<T, R> Clone for Fail<T, R>{
+impl<T, R, E> Clone for Fail<T, R, E>{
     fn clone(&self)->Self {
-        Fail{message:self.message.clone(), input_type:PhantomData, output_type:PhantomData}
+        Fail{message:self.message.clone(), input_type:PhantomData, output_type:PhantomData, error_type:PhantomData}
     }
 
     fn clone_from(&mut self, source: &Self) {
@@ -437,14 +595,414 @@ impl<T, R> Clone for Fail<T, R>{
     }
 }
 
-impl<T, R> Debug for Fail<T, R> where T:Clone, R:Clone {
+impl<T, R, E> Debug for Fail<T, R, E> where T:Clone, R:Clone {
     fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
         write!(formatter, "<fail parsec: {:?}>", self.message)
     }
 }
 
-impl<T:'static, R:'static> M<T, R> for Fail<T, R> where T:Clone, R:Clone{}
+impl<'a, T:'a, R:'a, E:'static+Error+From<ParseError>> M<'a, T, R, E> for Fail<T, R, E>
+where T:Clone, R:Clone{}
 
 pub fn fail<T, R>(message:String) -> Fail<T, R> where T:Clone, R:Clone {
     Fail::new(message)
 }
+
+pub fn fail_with<T, R, E>(message:String) -> Fail<T, R, E> where T:Clone, R:Clone {
+    Fail::new(message)
+}
+
+// Returns a copy of the user state, fails if it isn't set or isn't a U.
+pub struct GetState<T, U, E=SimpleError>{
+    input_type: PhantomData<T>,
+    output_type: PhantomData<U>,
+    error_type: PhantomData<E>,
+}
+
+impl<T, U, E> GetState<T, U, E> where U:Any+Clone {
+    pub fn new() -> GetState<T, U, E> {
+        GetState{input_type:PhantomData, output_type:PhantomData, error_type:PhantomData}
+    }
+}
+
+impl<T, U, E> Parsec<T, U, E> for GetState<T, U, E> where U:Any+Clone, E:Error+From<ParseError> {
+    fn parse(&self, state:&mut State<T>)->Status<U, E>{
+        match state.user_state() {
+            Some(user) => match user.downcast_ref::<U>() {
+                Some(value) => Ok(value.clone()),
+                None => {
+                    let err = ParseError::at(state.source_pos(), String::from("user state type mismatch"));
+                    Err(E::from(state.failure(err)))
+                },
+            },
+            None => {
+                let err = ParseError::at(state.source_pos(), String::from("user state is not set"));
+                Err(E::from(state.failure(err)))
+            },
+        }
+    }
+    fn reply(&self, state:&mut State<T>)->Reply<U, E>{
+        Reply::Empty(self.parse(state))
+    }
+}
+
+impl<'a, T, U, E> FnOnce<(&'a mut State<T>, )> for GetState<T, U, E>
+where U:Any+Clone, E:Error+From<ParseError> {
+    type Output = Status<U, E>;
+    extern "rust-call" fn call_once(self, _: (&'a mut State<T>, )) -> Status<U, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, T, U, E> FnMut<(&'a mut State<T>, )> for GetState<T, U, E>
+where U:Any+Clone, E:Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'a mut State<T>, )) -> Status<U, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, T, U, E> Fn<(&'a mut State<T>, )> for GetState<T, U, E> where U:Any+Clone, E:Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'a mut State<T>, )) -> Status<U, E> {
+        let (state, ) = args;
+        self.parse(state)
+    }
+}
+
+impl<T, U, E> Clone for GetState<T, U, E> {
+    fn clone(&self)->Self {
+        GetState{input_type:PhantomData, output_type:PhantomData, error_type:PhantomData}
+    }
+
+    fn clone_from(&mut self, _: &Self) {
+    }
+}
+
+impl<T, U, E> Debug for GetState<T, U, E> {
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        write!(formatter, "<get state parsec>")
+    }
+}
+
+impl<'a, T:'a+Clone, U:'static+Any+Clone, E:'static+Error+From<ParseError>> M<'a, T, U, E> for GetState<T, U, E>{}
+
+pub fn get_state<T, U>() -> GetState<T, U> where U:Any+Clone {
+    GetState::new()
+}
+
+pub fn get_state_with<T, U, E>() -> GetState<T, U, E> where U:Any+Clone {
+    GetState::new()
+}
+
+// Replaces the user state with value.
+pub struct PutState<T, U, E=SimpleError>{
+    value: U,
+    input_type: PhantomData<T>,
+    error_type: PhantomData<E>,
+}
+
+impl<T, U, E> PutState<T, U, E> where U:Any+Clone {
+    pub fn new(value:U) -> PutState<T, U, E> {
+        PutState{value:value, input_type:PhantomData, error_type:PhantomData}
+    }
+}
+
+impl<T, U, E> Parsec<T, (), E> for PutState<T, U, E> where U:Any+Clone, E:Error+From<ParseError> {
+    fn parse(&self, state:&mut State<T>)->Status<(), E>{
+        state.set_user_state(Some(Arc::new(self.value.clone())));
+        Ok(())
+    }
+    fn reply(&self, state:&mut State<T>)->Reply<(), E>{
+        Reply::Empty(self.parse(state))
+    }
+}
+
+impl<'a, T, U, E> FnOnce<(&'a mut State<T>, )> for PutState<T, U, E>
+where U:Any+Clone, E:Error+From<ParseError> {
+    type Output = Status<(), E>;
+    extern "rust-call" fn call_once(self, _: (&'a mut State<T>, )) -> Status<(), E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, T, U, E> FnMut<(&'a mut State<T>, )> for PutState<T, U, E>
+where U:Any+Clone, E:Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'a mut State<T>, )) -> Status<(), E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, T, U, E> Fn<(&'a mut State<T>, )> for PutState<T, U, E> where U:Any+Clone, E:Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'a mut State<T>, )) -> Status<(), E> {
+        let (state, ) = args;
+        self.parse(state)
+    }
+}
+
+impl<T, U, E> Clone for PutState<T, U, E> where U:Clone {
+    fn clone(&self)->Self {
+        PutState{value:self.value.clone(), input_type:PhantomData, error_type:PhantomData}
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.value = source.value.clone();
+    }
+}
+
+impl<T, U, E> Debug for PutState<T, U, E> {
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        write!(formatter, "<put state parsec>")
+    }
+}
+
+impl<'a, T:'a+Clone, U:'static+Any+Clone, E:'static+Error+From<ParseError>> M<'a, T, (), E> for PutState<T, U, E>{}
+
+pub fn put_state<T, U>(value:U) -> PutState<T, U> where U:Any+Clone {
+    PutState::new(value)
+}
+
+pub fn put_state_with<T, U, E>(value:U) -> PutState<T, U, E> where U:Any+Clone {
+    PutState::new(value)
+}
+
+// Replaces the user state with f(state), fails if it isn't set or isn't a U.
+pub struct ModifyState<T, U, E=SimpleError>{
+    f: Arc<Fn(U)->U>,
+    input_type: PhantomData<T>,
+    error_type: PhantomData<E>,
+}
+
+impl<T, U, E> ModifyState<T, U, E> where U:Any+Clone {
+    pub fn new(f:Arc<Fn(U)->U>) -> ModifyState<T, U, E> {
+        ModifyState{f:f, input_type:PhantomData, error_type:PhantomData}
+    }
+}
+
+impl<T, U, E> Parsec<T, (), E> for ModifyState<T, U, E> where U:Any+Clone, E:Error+From<ParseError> {
+    fn parse(&self, state:&mut State<T>)->Status<(), E>{
+        let value = try!(GetState::<T, U, E>::new().parse(state));
+        state.set_user_state(Some(Arc::new((self.f)(value))));
+        Ok(())
+    }
+    fn reply(&self, state:&mut State<T>)->Reply<(), E>{
+        Reply::Empty(self.parse(state))
+    }
+}
+
+impl<'a, T, U, E> FnOnce<(&'a mut State<T>, )> for ModifyState<T, U, E>
+where U:Any+Clone, E:Error+From<ParseError> {
+    type Output = Status<(), E>;
+    extern "rust-call" fn call_once(self, _: (&'a mut State<T>, )) -> Status<(), E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, T, U, E> FnMut<(&'a mut State<T>, )> for ModifyState<T, U, E>
+where U:Any+Clone, E:Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'a mut State<T>, )) -> Status<(), E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, T, U, E> Fn<(&'a mut State<T>, )> for ModifyState<T, U, E>
+where U:Any+Clone, E:Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'a mut State<T>, )) -> Status<(), E> {
+        let (state, ) = args;
+        self.parse(state)
+    }
+}
+
+impl<T, U, E> Clone for ModifyState<T, U, E> {
+    fn clone(&self)->Self {
+        ModifyState{f:self.f.clone(), input_type:PhantomData, error_type:PhantomData}
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.f = source.f.clone();
+    }
+}
+
+impl<T, U, E> Debug for ModifyState<T, U, E> {
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        write!(formatter, "<modify state parsec>")
+    }
+}
+
+impl<'a, T:'a+Clone, U:'static+Any+Clone, E:'static+Error+From<ParseError>> M<'a, T, (), E> for ModifyState<T, U, E>{}
+
+pub fn modify_state<T, U>(f:Arc<Fn(U)->U>) -> ModifyState<T, U> where U:Any+Clone {
+    ModifyState::new(f)
+}
+
+pub fn modify_state_with<T, U, E>(f:Arc<Fn(U)->U>) -> ModifyState<T, U, E> where U:Any+Clone {
+    ModifyState::new(f)
+}
+
+// Token matches a token equals to kind, it just needs Debug, no Display as Equal.
+// A mismatched token is not consumed.
+pub struct Token<T, E=SimpleError>{
+    kind : T,
+    error_type: PhantomData<E>,
+}
+
+impl<T, E> Token<T, E> where T:PartialEq+Debug+Clone {
+    pub fn new(kind:T) -> Token<T, E> {
+        Token{kind:kind, error_type:PhantomData}
+    }
+}
+
+impl<T, E> Parsec<T, T, E> for Token<T, E> where T:PartialEq+Debug+Clone, E:Error+From<ParseError> {
+    fn parse(&self, state:&mut State<T>)->Status<T, E>{
+        match state.peek() {
+            Some(token) => if token == self.kind {
+                state.next();
+                Ok(token)
+            } else {
+                let err = ParseError::unexpected_in(state.span(), format!("{:?}", token));
+                Err(E::from(state.failure(err.expect(format!("{:?}", self.kind)))))
+            },
+            None => Err(E::from(state.failure(state.eof_error().expect(format!("{:?}", self.kind))))),
+        }
+    }
+    fn reply(&self, state:&mut State<T>)->Reply<T, E>{
+        Reply::token(self.parse(state))
+    }
+}
+
+impl<'a, T, E> FnOnce<(&'a mut State<T>, )> for Token<T, E>
+where T:PartialEq+Debug+Clone, E:Error+From<ParseError> {
+    type Output = Status<T, E>;
+    extern "rust-call" fn call_once(self, _: (&'a mut State<T>, )) -> Status<T, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, T, E> FnMut<(&'a mut State<T>, )> for Token<T, E>
+where T:PartialEq+Debug+Clone, E:Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'a mut State<T>, )) -> Status<T, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, T, E> Fn<(&'a mut State<T>, )> for Token<T, E>
+where T:PartialEq+Debug+Clone, E:Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'a mut State<T>, )) -> Status<T, E> {
+        let (state, ) = args;
+        self.parse(state)
+    }
+}
+
+impl<T, E> Clone for Token<T, E> where T:Clone {
+    fn clone(&self)->Self {
+        Token{kind:self.kind.clone(), error_type:PhantomData}
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.kind = source.kind.clone();
+    }
+}
+
+impl<T, E> Debug for Token<T, E> where T:Debug {
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        write!(formatter, "<token parsec({:?})>", self.kind)
+    }
+}
+
+impl<'a, T:'a+PartialEq+Debug+Clone, E:'static+Error+From<ParseError>> M<'a, T, T, E> for Token<T, E>{}
+
+pub fn token<T>(kind:T) -> Token<T> where T:PartialEq+Debug+Clone {
+    Token::new(kind)
+}
+
+pub fn token_with<T, E>(kind:T) -> Token<T, E> where T:PartialEq+Debug+Clone {
+    Token::new(kind)
+}
+
+// SatisfyMap matches a token f maps to Some, and returns the mapped value, so it could
+// extract the payload of a token. A mismatched token is not consumed.
+pub struct SatisfyMap<T, R, E=SimpleError>{
+    description: Arc<String>,
+    f: Arc<Fn(&T)->Option<R>>,
+    error_type: PhantomData<E>,
+}
+
+impl<T, R, E> SatisfyMap<T, R, E> where T:Debug+Clone {
+    pub fn new(description:String, f:Arc<Fn(&T)->Option<R>>) -> SatisfyMap<T, R, E> {
+        SatisfyMap{description:Arc::new(description), f:f, error_type:PhantomData}
+    }
+}
+
+impl<T, R, E> Parsec<T, R, E> for SatisfyMap<T, R, E> where T:Debug+Clone, E:Error+From<ParseError> {
+    fn parse(&self, state:&mut State<T>)->Status<R, E>{
+        match state.peek() {
+            Some(token) => match (self.f)(&token) {
+                Some(value) => {
+                    state.next();
+                    Ok(value)
+                },
+                None => {
+                    let err = ParseError::unexpected_in(state.span(), format!("{:?}", token));
+                    Err(E::from(state.failure(err.expect(String::from(self.description.as_str())))))
+                }
+            },
+            None => {
+                let err = state.eof_error().expect(String::from(self.description.as_str()));
+                Err(E::from(state.failure(err)))
+            },
+        }
+    }
+    fn reply(&self, state:&mut State<T>)->Reply<R, E>{
+        Reply::token(self.parse(state))
+    }
+}
+
+impl<'a, T, R, E> FnOnce<(&'a mut State<T>, )> for SatisfyMap<T, R, E>
+where T:Debug+Clone, E:Error+From<ParseError> {
+    type Output = Status<R, E>;
+    extern "rust-call" fn call_once(self, _: (&'a mut State<T>, )) -> Status<R, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, T, R, E> FnMut<(&'a mut State<T>, )> for SatisfyMap<T, R, E>
+where T:Debug+Clone, E:Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'a mut State<T>, )) -> Status<R, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, T, R, E> Fn<(&'a mut State<T>, )> for SatisfyMap<T, R, E>
+where T:Debug+Clone, E:Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'a mut State<T>, )) -> Status<R, E> {
+        let (state, ) = args;
+        self.parse(state)
+    }
+}
+
+impl<T, R, E> Clone for SatisfyMap<T, R, E> {
+    fn clone(&self)->Self {
+        SatisfyMap{description:self.description.clone(), f:self.f.clone(), error_type:PhantomData}
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.description = source.description.clone();
+        self.f = source.f.clone();
+    }
+}
+
+impl<T, R, E> Debug for SatisfyMap<T, R, E> {
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        write!(formatter, "<satisfy map parsec: {}>", self.description)
+    }
+}
+
+impl<'a, T:'a+Debug+Clone, R:'a+Clone, E:'static+Error+From<ParseError>> M<'a, T, R, E> for SatisfyMap<T, R, E>{}
+
+pub fn satisfy_map<T, R>(description:String, f:Arc<Fn(&T)->Option<R>>) -> SatisfyMap<T, R>
+        where T:Debug+Clone {
+    SatisfyMap::new(description, f)
+}
+
+pub fn satisfy_map_with<T, R, E>(description:String, f:Arc<Fn(&T)->Option<R>>) -> SatisfyMap<T, R, E>
+        where T:Debug+Clone {
+    SatisfyMap::new(description, f)
+}
@@ -0,0 +1,1498 @@
+use parsec::{State, Error, ParseError, SimpleError, Parsec, Status, Reply, Span, Monad, monad, M, parser, drop_error, relabel_dropped, rewrite_farthest};
+use parsec::atom::{Pack, Fail};
+use parsec::expr::Binary;
+use std::sync::Arc;
+use std::ops::Range;
+use std::mem;
+use std::fmt::{Debug, Formatter};
+use std::fmt;
+
+pub struct Try<'a, T, R, E=SimpleError>{
+    parsec : Arc<Parsec<T, R, E>+'a>,
+}
+
+impl<'a, T, R, E> Try<'a, T, R, E> where T:Clone {
+    pub fn new(p:Arc<Parsec<T, R, E>+'a>) -> Try<'a, T, R, E> {
+        Try{parsec:p.clone()}
+    }
+}
+
+impl<'a, T, R, E> Parsec<T, R, E> for Try<'a, T, R, E> where T:Clone, E:Error+From<ParseError> {
+    fn parse(&self, state: &mut State<T>)->Status<R, E> {
+        self.reply(state).status()
+    }
+    fn reply(&self, state: &mut State<T>)->Reply<R, E> {
+        let checkpoint = state.checkpoint();
+        match self.parsec.reply(state) {
+            Reply::Consumed(Err(err)) | Reply::Empty(Err(err)) => if err.is_fatal() {
+                Reply::Consumed(Err(err))
+            } else {
+                match state.rollback(checkpoint) {
+                    Ok(_) => Reply::Empty(Err(err)),
+                    Err(rollback) => Reply::Consumed(Err(E::from(rollback))),
+                }
+            },
+            reply => reply,
+        }
+    }
+}
+
+impl<'a, 'b, T, R, E> FnOnce<(&'b mut State<T>, )> for Try<'a, T, R, E> where T:Clone, E:Error+From<ParseError> {
+    type Output = Status<R, E>;
+    extern "rust-call" fn call_once(self, _: (&'b mut State<T>, )) -> Status<R, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, R, E> FnMut<(&'b mut State<T>, )> for Try<'a, T, R, E> where T:Clone, E:Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'b mut State<T>, )) -> Status<R, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, R, E> Fn<(&'b mut State<T>, )> for Try<'a, T, R, E> where T:Clone, E:Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'b mut State<T>, )) -> Status<R, E> {
+        let (state, ) = args;
+        self.parse(state)
+    }
+}
+
+impl<'a, T, R, E> Clone for Try<'a, T, R, E> where T:Clone {
+    fn clone(&self)->Self {
+        Try{parsec:self.parsec.clone()}
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.parsec = source.parsec.clone();
+    }
+}
+
+impl<'a, T, R, E> Debug for Try<'a, T, R, E> where T:Clone{
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        "<try parsec>".fmt(formatter)
+    }
+}
+
+impl<'a, T:'a+Clone, R:'a+Clone, E:'static+Error+From<ParseError>> M<'a, T, R, E> for Try<'a, T, R, E>{}
+
+pub fn try<'a, T, R, E>(p:Arc<Parsec<T, R, E>+'a>) -> Try<'a, T, R, E> where T:Clone {
+    Try::new(p)
+}
+
+// Either tries y when x fails without consuming. If y then succeeds without consuming too, the
+// error of x goes to drop_error, so sep_by keeps what its first item expected.
+pub struct Either<'a, T, R, E=SimpleError>{
+    x: Arc<Parsec<T, R, E>+'a>,
+    y: Arc<Parsec<T, R, E>+'a>,
+}
+
+impl<'a, T:'a, R:'a, E:'static> Either<'a, T, R, E> where T:Clone, E:Error+From<ParseError> {
+    pub fn new(x:Arc<Parsec<T, R, E>+'a>, y:Arc<Parsec<T, R, E>+'a>) -> Either<'a, T, R, E> {
+        Either{x:x.clone(), y:y.clone()}
+    }
+
+    pub fn or(&self, z:Arc<Parsec<T, R, E>+'a>)-> Either<'a, T, R, E> {
+        let left = Either{x:self.x.clone(), y:self.y.clone()};
+        Either::new(Arc::new(left), z.clone())
+    }
+}
+
+impl<'a, T, R, E> Parsec<T, R, E> for Either<'a, T, R, E> where T:Clone, E:'static+Error+From<ParseError> {
+    fn parse(&self, state:&mut State<T>)->Status<R, E> {
+        self.reply(state).status()
+    }
+    fn reply(&self, state:&mut State<T>)->Reply<R, E> {
+        let checkpoint = state.checkpoint();
+        match self.x.reply(state) {
+            Reply::Empty(Err(err)) => {
+                if err.is_fatal() {
+                    return Reply::Empty(Err(err));
+                }
+                // x may have changed the user state before it failed
+                if let Err(rollback) = state.rollback(checkpoint) {
+                    return Reply::Empty(Err(E::from(rollback)));
+                }
+                match self.y.reply(state) {
+                    // both failed without consuming, so the error says what both of them expect
+                    Reply::Empty(Err(other)) => Reply::Empty(Err(err.merge(other))),
+                    Reply::Empty(Ok(x)) => {
+                        drop_error(state, err);
+                        Reply::Empty(Ok(x))
+                    },
+                    reply => reply,
+                }
+            },
+            reply => reply,
+        }
+    }
+}
+
+impl<'a, 'b, T, R, E> FnOnce<(&'b mut State<T>, )> for Either<'a, T, R, E> where T:Clone, E:'static+Error+From<ParseError> {
+    type Output = Status<R, E>;
+    extern "rust-call" fn call_once(self, _: (&'b mut State<T>, )) -> Status<R, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, R, E> FnMut<(&'b mut State<T>, )> for Either<'a, T, R, E> where T:Clone, E:'static+Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'b mut State<T>, )) -> Status<R, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, R, E> Fn<(&'b mut State<T>, )> for Either<'a, T, R, E> where T:Clone, E:'static+Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'b mut State<T>, )) -> Status<R, E> {
+        //self.call_once(args)
+        let (state, ) = args;
+        self.parse(state)
+    }
+}
+
+impl<'a, T, R, E> Clone for Either<'a, T, R, E> where T:Clone {
+    fn clone(&self)->Self {
+        Either{x:self.x.clone(), y:self.y.clone()}
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.x = source.x.clone();
+        self.y = source.y.clone();
+    }
+}
+
+impl<'a, T, R, E> Debug for Either<'a, T, R, E> where T:Clone {
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        "<either parsec>".fmt(formatter)
+    }
+}
+
+impl<'a, T:'a+Clone, R:'a+Clone, E:'static+Error+From<ParseError>> M<'a, T, R, E> for Either<'a, T, R, E>{}
+
+pub fn either<'a, T:'a, R:'a, E:'static>(x: Arc<Parsec<T, R, E>+'a>, y:Arc<Parsec<T, R, E>+'a>)->Either<'a, T, R, E>
+where T:Clone, E:Error+From<ParseError> {
+    Either::new(x, y)
+}
+
+// Many parses p until it fails without consuming input, and passes that error to drop_error. If
+// p fails after consuming, many fails with it, wrap p with try to backtrack.
+pub struct Many<'a, T, R, E=SimpleError> {
+    parsec: Arc<Parsec<T, R, E>+'a>,
+}
+
+impl<'a, T, R, E> Many<'a, T, R, E> where T:Clone, R:Clone+Debug {
+    pub fn new(p:Arc<Parsec<T, R, E>+'a>) -> Many<'a, T, R, E> {
+        Many{parsec:p.clone()}
+    }
+}
+
+impl<'a, T, R, E> Parsec<T, Vec<R>, E> for Many<'a, T, R, E> where T:Clone, R:Clone+Debug, E:'static+Error+From<ParseError> {
+    fn parse(&self, state:&mut State<T>)->Status<Vec<R>, E> {
+        self.reply(state).status()
+    }
+    fn reply(&self, state:&mut State<T>)->Reply<Vec<R>, E> {
+        let mut re = Vec::new();
+        let mut consumed = false;
+        loop {
+            let checkpoint = state.checkpoint();
+            match self.parsec.reply(state) {
+                Reply::Consumed(Ok(x)) => {
+                    consumed = true;
+                    re.push(x);
+                },
+                Reply::Empty(Ok(_)) => {
+                    let message = String::from("many is applied to a parser that accepts empty input");
+                    return Reply::new(consumed, Err(E::from(state.failure(ParseError::at(state.source_pos(), message)))));
+                },
+                Reply::Consumed(Err(err)) => return Reply::Consumed(Err(err)),
+                Reply::Empty(Err(err)) => {
+                    if err.is_fatal() {
+                        return Reply::new(consumed, Err(err));
+                    }
+                    return match state.rollback(checkpoint) {
+                        Ok(_) => {
+                            drop_error(state, err);
+                            Reply::new(consumed, Ok(re))
+                        },
+                        Err(err) => Reply::new(consumed, Err(E::from(err))),
+                    };
+                }
+            }
+        }
+    }
+}
+
+impl<'a, 'b, T, R, E> FnOnce<(&'b mut State<T>, )> for Many<'a, T, R, E>
+where T:Clone, R:Clone+Debug, E:'static+Error+From<ParseError> {
+    type Output = Status<Vec<R>, E>;
+    extern "rust-call" fn call_once(self, _: (&'b mut State<T>, )) -> Status<Vec<R>, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, R, E> FnMut<(&'b mut State<T>, )> for Many<'a, T, R, E>
+where T:Clone, R:Clone+Debug, E:'static+Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'b mut State<T>, )) -> Status<Vec<R>, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, R, E> Fn<(&'b mut State<T>, )> for Many<'a, T, R, E>
+where T:Clone, R:Clone+Debug, E:'static+Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'b mut State<T>, )) -> Status<Vec<R>, E> {
+        let (state, ) = args;
+        self.parse(state)
+    }
+}
+
+impl<'a, T, R, E> Clone for Many<'a, T, R, E> where T:Clone, R:Clone+Debug {
+    fn clone(&self)->Self {
+        Many{parsec:self.parsec.clone()}
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.parsec = source.parsec.clone();
+    }
+}
+
+impl<'a, T, R, E> Debug for Many<'a, T, R, E> where T:Clone, R:Clone+Debug {
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        "<many parsec>".fmt(formatter)
+    }
+}
+
+impl<'a, T:'a+Clone, R:'a+Clone+Debug, E:'static+Error+From<ParseError>> M<'a, T, Vec<R>, E> for Many<'a, T, R, E>{}
+
+pub fn many<'a, T:'a, R:'a, E:'static>(p:Arc<Parsec<T, R, E>+'a>)->Many<'a, T, R, E>
+where T:Clone, R:Clone+Debug {
+    Many::new(p)
+}
+
+pub fn many1<'a, T:'a, R:'a, E:'static>(p:Arc<Parsec<T, R, E>+'a>)->Monad<'a, T, R, Vec<R>, E>
+where T:Clone, R:Clone+Debug, E:Error+From<ParseError> {
+    parser(p.clone()).bind(Arc::new(Box::new(move |state: &mut State<T>, x: R| -> Status<Vec<R>, E> {
+        let mut rev = Vec::new();
+        let tail = many(p.clone()).parse(state);
+        let data = try!(tail);
+        rev.push(x);
+        rev.push_all(&data);
+        Ok(rev)
+    })))
+}
+
+pub fn between<'a, T:'a, B:'a, P:'a, End:'a, E:'static>
+        (begin:Arc<Parsec<T, B, E>+'a>, parsec:Arc<Parsec<T, P, E>+'a>, end:Arc<Parsec<T, End, E>+'a>)
+        ->Monad<'a, T, P, P, E> where T:Clone, P:Clone, B:Clone, End:Clone, E:Error+From<ParseError> {
+    // TODO: A fake binder between begin and parsec then, someone manybe remove it.
+    parser(begin).then(parsec).over(end)
+}
+
+pub fn otherwise<'a, T:'a, R:'a, E:'static>(p:Arc<Parsec<T, R, E>+'a>, message:String)->Either<'a, T, R, E>
+where T:Clone, R:Clone, E:Error+From<ParseError> {
+    either(p.clone(), Arc::new(Fail::new(message)))
+}
+
+pub fn many_tail<'a, T:'a, R:'a, Tail:'a, E:'static>(p:Arc<Parsec<T, R, E>+'a>, tail:Arc<Parsec<T, Tail, E>+'a>)
+    ->Monad<'a, T, Vec<R>, Vec<R>, E>
+where T:Clone, R:Clone+Debug, Tail:Clone, E:Error+From<ParseError> {
+    // TODO: A fake binder between p and tail, someone manybe remove it.
+    parser(Arc::new(many(p))).over(tail)
+}
+
+pub fn many1_tail<'a, T:'a, R:'a, Tail:'a, E:'static>(p:Arc<Parsec<T, R, E>+'a>, tail:Arc<Parsec<T, Tail, E>+'a>)
+    ->Monad<'a, T, Vec<R>, Vec<R>, E>
+where T:Clone, R:Clone+Debug, Tail:Clone, E:Error+From<ParseError> {
+    // TODO: A fake binder between p and tail, someone manybe remove it.
+    parser(Arc::new(many1(p))).over(tail)
+}
+
+// We can use many/many1 as skip, but them more effective. It drops the error of p as many does.
+pub struct Skip<'a, T, R, E=SimpleError> {
+    parsec: Arc<Parsec<T, R, E>+'a>,
+}
+
+impl<'a, T, R, E> Skip<'a, T, R, E> where T:Clone, R:Clone+Debug {
+    pub fn new(p:Arc<Parsec<T, R, E>+'a>) -> Skip<'a, T, R, E> {
+        Skip{parsec:p.clone()}
+    }
+}
+
+impl<'a, T:'a, R:'a, E:'static> Parsec<T, Vec<R>, E> for Skip<'a, T, R, E>
+where T:Clone, R:Clone+Debug, E:Error+From<ParseError> {
+    fn parse(&self, state:&mut State<T>)->Status<Vec<R>, E> {
+        self.reply(state).status()
+    }
+    fn reply(&self, state:&mut State<T>)->Reply<Vec<R>, E> {
+        let mut consumed = false;
+        loop {
+            let checkpoint = state.checkpoint();
+            match self.parsec.reply(state) {
+                Reply::Consumed(Ok(_)) => consumed = true,
+                Reply::Empty(Ok(_)) => {
+                    let message = String::from("skip_many is applied to a parser that accepts empty input");
+                    return Reply::new(consumed, Err(E::from(state.failure(ParseError::at(state.source_pos(), message)))));
+                },
+                Reply::Consumed(Err(err)) => return Reply::Consumed(Err(err)),
+                Reply::Empty(Err(err)) => {
+                    if err.is_fatal() {
+                        return Reply::new(consumed, Err(err));
+                    }
+                    return match state.rollback(checkpoint) {
+                        Ok(_) => {
+                            drop_error(state, err);
+                            Reply::new(consumed, Ok(Vec::new()))
+                        },
+                        Err(err) => Reply::new(consumed, Err(E::from(err))),
+                    };
+                }
+            }
+        }
+    }
+}
+
+impl<'a, 'b, T:'a, R:'a, E:'static> FnOnce<(&'b mut State<T>, )> for Skip<'a, T, R, E>
+where T:Clone, R:Clone+Debug, E:Error+From<ParseError> {
+    type Output = Status<Vec<R>, E>;
+    extern "rust-call" fn call_once(self, _: (&'b mut State<T>, )) -> Status<Vec<R>, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T:'a, R:'a, E:'static> FnMut<(&'b mut State<T>, )> for Skip<'a, T, R, E>
+where T:Clone, R:Clone+Debug, E:Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'b mut State<T>, )) -> Status<Vec<R>, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T:'a, R:'a, E:'static> Fn<(&'b mut State<T>, )> for Skip<'a, T, R, E>
+where T:Clone, R:Clone+Debug, E:Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'b mut State<T>, )) -> Status<Vec<R>, E> {
+        //self.call_once(args)
+        let (state, ) = args;
+        self.parse(state)
+    }
+}
+
+impl<'a, T, R, E> Clone for Skip<'a, T, R, E> where T:Clone, R:Clone+Debug {
+    fn clone(&self)->Self {
+        Skip{parsec:self.parsec.clone()}
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.parsec = source.parsec.clone();
+    }
+}
+
+impl<'a, T, R, E> Debug for Skip<'a, T, R, E> where T:Clone, R:Clone+Debug{
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        "<skip parsec>".fmt(formatter)
+    }
+}
+
+impl<'a, T:'a+Clone, R:'a+Clone+Debug, E:'static+Error+From<ParseError>> M<'a, T, Vec<R>, E> for Skip<'a, T, R, E>{}
+
+pub fn skip_many<'a, T:'a, R:'a, E:'static>(p:Arc<Parsec<T, R, E>+'a>)->Skip<'a, T, R, E>
+where T:Clone, R:Clone+Debug {
+    Skip::new(p)
+}
+
+pub struct Skip1<'a, T, R, E=SimpleError> {
+    parsec: Arc<Parsec<T, R, E>+'a>,
+}
+
+impl<'a, T, R, E> Skip1<'a, T, R, E> where T:Clone, R:Clone+Debug {
+    pub fn new(p:Arc<Parsec<T, R, E>+'a>) -> Skip1<'a, T, R, E> {
+        Skip1{parsec:p.clone()}
+    }
+}
+
+impl<'a, T:'a, R:'a, E:'static> Parsec<T, Vec<R>, E> for Skip1<'a, T, R, E>
+where T:Clone, R:Clone+Debug, E:Error+From<ParseError> {
+    fn parse(&self, state:&mut State<T>)->Status<Vec<R>, E> {
+        self.reply(state).status()
+    }
+    fn reply(&self, state:&mut State<T>)->Reply<Vec<R>, E> {
+        match self.parsec.reply(state) {
+            Reply::Consumed(Ok(_)) => Reply::Consumed(skip_many(self.parsec.clone()).parse(state)),
+            Reply::Empty(Ok(_)) => skip_many(self.parsec.clone()).reply(state),
+            Reply::Consumed(Err(err)) => Reply::Consumed(Err(err)),
+            Reply::Empty(Err(err)) => Reply::Empty(Err(err)),
+        }
+    }
+}
+
+impl<'a, T, R, E> Clone for Skip1<'a, T, R, E> where T:Clone, R:Clone+Debug {
+    fn clone(&self)->Self {
+        Skip1{parsec:self.parsec.clone()}
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.parsec = source.parsec.clone();
+    }
+}
+
+impl<'a, T, R, E> Debug for Skip1<'a, T, R, E> where T:Clone, R:Clone+Debug{
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        "<many1 parsec>".fmt(formatter)
+    }
+}
+
+impl<'a, 'b, T:'a, R:'a, E:'static> FnOnce<(&'b mut State<T>, )> for Skip1<'a, T, R, E>
+where T:Clone, R:Clone+Debug, E:Error+From<ParseError> {
+    type Output = Status<Vec<R>, E>;
+    extern "rust-call" fn call_once(self, _: (&'b mut State<T>, )) -> Status<Vec<R>, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T:'a, R:'a, E:'static> FnMut<(&'b mut State<T>, )> for Skip1<'a, T, R, E>
+where T:Clone, R:Clone+Debug, E:Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'b mut State<T>, )) -> Status<Vec<R>, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T:'a, R:'a, E:'static> Fn<(&'b mut State<T>, )> for Skip1<'a, T, R, E>
+where T:Clone, R:Clone+Debug, E:Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'b mut State<T>, )) -> Status<Vec<R>, E> {
+        //self.call_once(args)
+        let (state, ) = args;
+        self.parse(state)
+    }
+}
+
+impl<'a, T:'a+Clone, R:'a+Clone+Debug, E:'static+Error+From<ParseError>> M<'a, T, Vec<R>, E> for Skip1<'a, T, R, E>{}
+
+pub fn skip_many1<'a, T:'a, R:'a, E:'static>(p:Arc<Parsec<T, R, E>+'a>)->Skip1<'a, T, R, E>
+where T:Clone, R:Clone+Debug {
+    Skip1::new(p)
+}
+
+// Repeat parses p between min and max times, max None for no bound. It stops at max, or when
+// p fails without consuming once min items are there, and passes that error to drop_error. If
+// p fails before min items, the error gets a message telling how many items were found. The
+// skip variants drop the items and return an empty Vec as skip_many does. A max less than min
+// fails without consuming.
+pub struct Repeat<'a, T, R, E=SimpleError> {
+    parsec: Arc<Parsec<T, R, E>+'a>,
+    min: usize,
+    max: Option<usize>,
+    skip: bool,
+}
+
+impl<'a, T, R, E> Repeat<'a, T, R, E> {
+    pub fn new(p:Arc<Parsec<T, R, E>+'a>, min:usize, max:Option<usize>, skip:bool) -> Repeat<'a, T, R, E> {
+        Repeat{parsec:p.clone(), min:min, max:max, skip:skip}
+    }
+
+    fn shortage(&self, found:usize)->String {
+        let expected = match self.max {
+            Some(max) if max == self.min => format!("{}", max),
+            Some(max) => format!("{} to {}", self.min, max),
+            None => format!("at least {}", self.min),
+        };
+        format!("expected {} items, found {}", expected, found)
+    }
+
+    // The farthest failure is where p failed too, it tells the count as well.
+    fn short(&self, state:&mut State<T>, before:Option<Arc<ParseError>>, found:usize, err:E)->E where E:Error {
+        let message = self.shortage(found);
+        rewrite_farthest(state, before, Some(err.pos()), |farthest| farthest.with_message(&message));
+        err.with_message(&message)
+    }
+}
+
+impl<'a, T, R, E> Parsec<T, Vec<R>, E> for Repeat<'a, T, R, E> where E:'static+Error+From<ParseError> {
+    fn parse(&self, state:&mut State<T>)->Status<Vec<R>, E> {
+        self.reply(state).status()
+    }
+    fn reply(&self, state:&mut State<T>)->Reply<Vec<R>, E> {
+        if let Some(max) = self.max {
+            if max < self.min {
+                let message = format!("repeat with max {} less than min {}", max, self.min);
+                return Reply::Empty(Err(E::from(state.failure(ParseError::at(state.source_pos(), message)))));
+            }
+        }
+        let before = state.aux().farthest.clone();
+        let mut re = Vec::new();
+        let mut found = 0;
+        let mut consumed = false;
+        while self.max.map_or(true, |max| found < max) {
+            let checkpoint = state.checkpoint();
+            match self.parsec.reply(state) {
+                Reply::Empty(Ok(_)) if self.max.is_none() => {
+                    let message = String::from("repeat is applied to a parser that accepts empty input");
+                    return Reply::new(consumed, Err(E::from(state.failure(ParseError::at(state.source_pos(), message)))));
+                },
+                Reply::Consumed(Ok(x)) | Reply::Empty(Ok(x)) => {
+                    consumed = consumed || state.pos() != checkpoint.pos;
+                    found += 1;
+                    if !self.skip {
+                        re.push(x);
+                    }
+                },
+                Reply::Consumed(Err(err)) => {
+                    let err = if found < self.min { self.short(state, before, found, err) } else { err };
+                    return Reply::Consumed(Err(err));
+                },
+                Reply::Empty(Err(err)) => {
+                    if found < self.min {
+                        return Reply::new(consumed, Err(self.short(state, before, found, err)));
+                    }
+                    if err.is_fatal() {
+                        return Reply::new(consumed, Err(err));
+                    }
+                    return match state.rollback(checkpoint) {
+                        Ok(_) => {
+                            drop_error(state, err);
+                            Reply::new(consumed, Ok(re))
+                        },
+                        Err(err) => Reply::new(consumed, Err(E::from(err))),
+                    };
+                },
+            }
+        }
+        Reply::new(consumed, Ok(re))
+    }
+}
+
+impl<'a, 'b, T, R, E> FnOnce<(&'b mut State<T>, )> for Repeat<'a, T, R, E> where E:'static+Error+From<ParseError> {
+    type Output = Status<Vec<R>, E>;
+    extern "rust-call" fn call_once(self, _: (&'b mut State<T>, )) -> Status<Vec<R>, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, R, E> FnMut<(&'b mut State<T>, )> for Repeat<'a, T, R, E> where E:'static+Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'b mut State<T>, )) -> Status<Vec<R>, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, R, E> Fn<(&'b mut State<T>, )> for Repeat<'a, T, R, E> where E:'static+Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'b mut State<T>, )) -> Status<Vec<R>, E> {
+        let (state, ) = args;
+        self.parse(state)
+    }
+}
+
+impl<'a, T, R, E> Clone for Repeat<'a, T, R, E> {
+    fn clone(&self)->Self {
+        Repeat{parsec:self.parsec.clone(), min:self.min, max:self.max, skip:self.skip}
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.parsec = source.parsec.clone();
+        self.min = source.min;
+        self.max = source.max;
+        self.skip = source.skip;
+    }
+}
+
+impl<'a, T, R, E> Debug for Repeat<'a, T, R, E> {
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        write!(formatter, "<repeat parsec({}, {:?})>", self.min, self.max)
+    }
+}
+
+impl<'a, T:'a+Clone, R:'a+Clone, E:'static+Error+From<ParseError>> M<'a, T, Vec<R>, E> for Repeat<'a, T, R, E>{}
+
+pub fn count<'a, T, R, E>(n:usize, p:Arc<Parsec<T, R, E>+'a>)->Repeat<'a, T, R, E> {
+    Repeat::new(p, n, Some(n), false)
+}
+
+pub fn many_m_n<'a, T, R, E>(min:usize, max:usize, p:Arc<Parsec<T, R, E>+'a>)->Repeat<'a, T, R, E> {
+    Repeat::new(p, min, Some(max), false)
+}
+
+pub fn at_least<'a, T, R, E>(n:usize, p:Arc<Parsec<T, R, E>+'a>)->Repeat<'a, T, R, E> {
+    Repeat::new(p, n, None, false)
+}
+
+pub fn at_most<'a, T, R, E>(n:usize, p:Arc<Parsec<T, R, E>+'a>)->Repeat<'a, T, R, E> {
+    Repeat::new(p, 0, Some(n), false)
+}
+
+pub fn skip_count<'a, T, R, E>(n:usize, p:Arc<Parsec<T, R, E>+'a>)->Repeat<'a, T, R, E> {
+    Repeat::new(p, n, Some(n), true)
+}
+
+pub fn skip_many_m_n<'a, T, R, E>(min:usize, max:usize, p:Arc<Parsec<T, R, E>+'a>)->Repeat<'a, T, R, E> {
+    Repeat::new(p, min, Some(max), true)
+}
+
+pub fn skip_at_least<'a, T, R, E>(n:usize, p:Arc<Parsec<T, R, E>+'a>)->Repeat<'a, T, R, E> {
+    Repeat::new(p, n, None, true)
+}
+
+pub fn skip_at_most<'a, T, R, E>(n:usize, p:Arc<Parsec<T, R, E>+'a>)->Repeat<'a, T, R, E> {
+    Repeat::new(p, 0, Some(n), true)
+}
+
+// SepEndBy parses p separated and optionally ended by sep, as sepEndBy of Haskell Parsec, so
+// lists may have a trailing separator. A p or sep failing after consuming fails it, the error
+// of the one ending the list goes to drop_error.
+pub struct SepEndBy<'a, T, Sep, R, E=SimpleError> {
+    sep: Arc<Parsec<T, Sep, E>+'a>,
+    parsec: Arc<Parsec<T, R, E>+'a>,
+    nonempty: bool,
+}
+
+impl<'a, T, Sep, R, E> SepEndBy<'a, T, Sep, R, E> {
+    pub fn new(sep:Arc<Parsec<T, Sep, E>+'a>, p:Arc<Parsec<T, R, E>+'a>, nonempty:bool) -> SepEndBy<'a, T, Sep, R, E> {
+        SepEndBy{sep:sep.clone(), parsec:p.clone(), nonempty:nonempty}
+    }
+}
+
+impl<'a, T, Sep, R, E> Parsec<T, Vec<R>, E> for SepEndBy<'a, T, Sep, R, E> where E:'static+Error+From<ParseError> {
+    fn parse(&self, state:&mut State<T>)->Status<Vec<R>, E> {
+        self.reply(state).status()
+    }
+    fn reply(&self, state:&mut State<T>)->Reply<Vec<R>, E> {
+        let mut re = Vec::new();
+        let mut consumed = false;
+        loop {
+            let start = state.pos();
+            let checkpoint = state.checkpoint();
+            match self.parsec.reply(state) {
+                Reply::Empty(Err(err)) => {
+                    if err.is_fatal() || (self.nonempty && re.is_empty()) {
+                        return Reply::new(consumed, Err(err));
+                    }
+                    return match state.rollback(checkpoint) {
+                        Ok(_) => {
+                            drop_error(state, err);
+                            Reply::new(consumed, Ok(re))
+                        },
+                        Err(err) => Reply::new(consumed, Err(E::from(err))),
+                    };
+                },
+                reply => {
+                    consumed = consumed || reply.is_consumed();
+                    match reply.status() {
+                        Ok(x) => re.push(x),
+                        Err(err) => return Reply::new(consumed, Err(err)),
+                    }
+                },
+            }
+            let checkpoint = state.checkpoint();
+            match self.sep.reply(state) {
+                Reply::Empty(Err(err)) => {
+                    if err.is_fatal() {
+                        return Reply::new(consumed, Err(err));
+                    }
+                    return match state.rollback(checkpoint) {
+                        Ok(_) => {
+                            drop_error(state, err);
+                            Reply::new(consumed, Ok(re))
+                        },
+                        Err(err) => Reply::new(consumed, Err(E::from(err))),
+                    };
+                },
+                reply => {
+                    consumed = consumed || reply.is_consumed();
+                    if let Err(err) = reply.status() {
+                        return Reply::new(consumed, Err(err));
+                    }
+                },
+            }
+            if state.pos() == start {
+                let message = String::from("sep_end_by is applied to parsers that accept empty input");
+                return Reply::new(consumed, Err(E::from(state.failure(ParseError::at(state.source_pos(), message)))));
+            }
+        }
+    }
+}
+
+impl<'a, 'b, T, Sep, R, E> FnOnce<(&'b mut State<T>, )> for SepEndBy<'a, T, Sep, R, E> where E:'static+Error+From<ParseError> {
+    type Output = Status<Vec<R>, E>;
+    extern "rust-call" fn call_once(self, _: (&'b mut State<T>, )) -> Status<Vec<R>, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, Sep, R, E> FnMut<(&'b mut State<T>, )> for SepEndBy<'a, T, Sep, R, E> where E:'static+Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'b mut State<T>, )) -> Status<Vec<R>, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, Sep, R, E> Fn<(&'b mut State<T>, )> for SepEndBy<'a, T, Sep, R, E> where E:'static+Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'b mut State<T>, )) -> Status<Vec<R>, E> {
+        let (state, ) = args;
+        self.parse(state)
+    }
+}
+
+impl<'a, T, Sep, R, E> Clone for SepEndBy<'a, T, Sep, R, E> {
+    fn clone(&self)->Self {
+        SepEndBy{sep:self.sep.clone(), parsec:self.parsec.clone(), nonempty:self.nonempty}
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.sep = source.sep.clone();
+        self.parsec = source.parsec.clone();
+        self.nonempty = source.nonempty;
+    }
+}
+
+impl<'a, T, Sep, R, E> Debug for SepEndBy<'a, T, Sep, R, E> {
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        write!(formatter, "<sep end by parsec>")
+    }
+}
+
+impl<'a, T:'a+Clone, Sep:'a, R:'a+Clone, E:'static+Error+From<ParseError>> M<'a, T, Vec<R>, E>
+for SepEndBy<'a, T, Sep, R, E>{}
+
+pub fn sep_end_by<'a, T, Sep, R, E>(sep:Arc<Parsec<T, Sep, E>+'a>, parsec:Arc<Parsec<T, R, E>+'a>)->SepEndBy<'a, T, Sep, R, E> {
+    SepEndBy::new(sep, parsec, false)
+}
+
+pub fn sep_end_by1<'a, T, Sep, R, E>(sep:Arc<Parsec<T, Sep, E>+'a>, parsec:Arc<Parsec<T, R, E>+'a>)->SepEndBy<'a, T, Sep, R, E> {
+    SepEndBy::new(sep, parsec, true)
+}
+
+// Every p ends with sep, as statements ended by semicolons.
+pub fn end_by<'a, T:'a, Sep:'a, R:'a, E:'static>(sep:Arc<Parsec<T, Sep, E>+'a>, parsec:Arc<Parsec<T, R, E>+'a>)
+    ->Many<'a, T, R, E>
+where T:Clone, R:Clone+Debug, Sep:Clone, E:Error+From<ParseError> {
+    many(Arc::new(parser(parsec).over(sep)))
+}
+
+pub fn end_by1<'a, T:'a, Sep:'a, R:'a, E:'static>(sep:Arc<Parsec<T, Sep, E>+'a>, parsec:Arc<Parsec<T, R, E>+'a>)
+    ->Monad<'a, T, R, Vec<R>, E>
+where T:Clone, R:Clone+Debug, Sep:Clone, E:Error+From<ParseError> {
+    many1(Arc::new(parser(parsec).over(sep)))
+}
+
+// Items between open and close separated by sep, empty lists and a trailing sep are allowed,
+// as [1, 2, 3,] or ().
+pub fn delimited_list<'a, T:'a, Open:'a, Sep:'a, R:'a, Close:'a, E:'static>
+        (open:Arc<Parsec<T, Open, E>+'a>, sep:Arc<Parsec<T, Sep, E>+'a>, item:Arc<Parsec<T, R, E>+'a>,
+         close:Arc<Parsec<T, Close, E>+'a>)
+        ->Monad<'a, T, Vec<R>, Vec<R>, E>
+where T:Clone, R:Clone, Open:Clone, Close:Clone, E:Error+From<ParseError> {
+    between(open, Arc::new(sep_end_by(sep, item)), close)
+}
+
+pub fn sep_by<'a, T:'a, Sep:'a, R:'a, E:'static>(sep:Arc<Parsec<T, Sep, E>+'a>, parsec:Arc<Parsec<T, R, E>+'a>)
+    ->Either<'a, T, Vec<R>, E>
+where T:Clone, R:Clone+Debug, Sep:Clone, E:Error+From<ParseError> {
+    let s = Arc::new(try(sep));
+    let p = Arc::new(try(parsec));
+    either(Arc::new(sep_by1(s, p)), Arc::new(Pack::new(Vec::new())))
+}
+
+pub fn sep_by1<'a, T:'a, Sep:'a, R:'a, E:'static>(sep:Arc<Parsec<T, Sep, E>+'a>, parsec:Arc<Parsec<T, R, E>+'a>)
+    ->Monad<'a, T, R, Vec<R>, E>
+where T:Clone, R:Clone+Debug, Sep:Clone, E:Error+From<ParseError> {
+    monad(parsec.clone()).bind(Arc::new(Box::new(move |state:&mut State<T>, x:R|->Status<Vec<R>, E>{
+        let mut rev = Vec::new();
+        let item = parser(sep.clone()).then(parsec.clone());
+        let tail = many(Arc::new(try(Arc::new(item)))).parse(state);
+        let data = try!(tail);
+        rev.push(x);
+        rev.push_all(&data);
+        Ok(rev)
+    })))
+}
+
+// Recognize returns the input p consumed instead of p's result. recognize gives its positions,
+// recognize_str and recognize_slice the piece of the source itself, without copy and with the
+// lifetime of the source, so recognized text can be returned inside a bind chain and outlive the
+// parse. The source must be the one the state parses, StrState and Utf8State positions are byte
+// offsets and SliceState positions indexes of it.
+pub struct Recognize<'a, T, R, S, E=SimpleError> {
+    parsec: Arc<Parsec<T, R, E>+'a>,
+    slice: Arc<Fn(usize, usize)->S+'a>,
+}
+
+impl<'a, T, R, S, E> Recognize<'a, T, R, S, E> {
+    pub fn new(p:Arc<Parsec<T, R, E>+'a>, slice:Arc<Fn(usize, usize)->S+'a>) -> Recognize<'a, T, R, S, E> {
+        Recognize{parsec:p.clone(), slice:slice}
+    }
+}
+
+impl<'a, T, R, S, E> Parsec<T, S, E> for Recognize<'a, T, R, S, E> where E:Error+From<ParseError> {
+    fn parse(&self, state:&mut State<T>)->Status<S, E> {
+        self.reply(state).status()
+    }
+    fn reply(&self, state:&mut State<T>)->Reply<S, E> {
+        let from = state.pos();
+        let re = self.parsec.reply(state);
+        let to = state.pos();
+        re.map(|_| (self.slice)(from, to))
+    }
+}
+
+impl<'a, 'b, T, R, S, E> FnOnce<(&'b mut State<T>, )> for Recognize<'a, T, R, S, E> where E:Error+From<ParseError> {
+    type Output = Status<S, E>;
+    extern "rust-call" fn call_once(self, _: (&'b mut State<T>, )) -> Status<S, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, R, S, E> FnMut<(&'b mut State<T>, )> for Recognize<'a, T, R, S, E> where E:Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'b mut State<T>, )) -> Status<S, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, R, S, E> Fn<(&'b mut State<T>, )> for Recognize<'a, T, R, S, E> where E:Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'b mut State<T>, )) -> Status<S, E> {
+        let (state, ) = args;
+        self.parse(state)
+    }
+}
+
+impl<'a, T, R, S, E> Clone for Recognize<'a, T, R, S, E> {
+    fn clone(&self)->Self {
+        Recognize{parsec:self.parsec.clone(), slice:self.slice.clone()}
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.parsec = source.parsec.clone();
+        self.slice = source.slice.clone();
+    }
+}
+
+impl<'a, T, R, S, E> Debug for Recognize<'a, T, R, S, E> {
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        "<recognize parsec>".fmt(formatter)
+    }
+}
+
+impl<'a, T:'a+Clone, R:'a, S:'a+Clone, E:'static+Error+From<ParseError>> M<'a, T, S, E> for Recognize<'a, T, R, S, E>{}
+
+pub fn recognize<'a, T, R, E>(p:Arc<Parsec<T, R, E>+'a>)->Recognize<'a, T, R, Range<usize>, E> {
+    Recognize::new(p, Arc::new(|from, to| from..to))
+}
+
+pub fn recognize_str<'a, T, R, E>(source:&'a str, p:Arc<Parsec<T, R, E>+'a>)->Recognize<'a, T, R, &'a str, E> {
+    Recognize::new(p, Arc::new(move |from, to| &source[from..to]))
+}
+
+pub fn recognize_slice<'a, X, T, R, E>(source:&'a [X], p:Arc<Parsec<T, R, E>+'a>)->Recognize<'a, T, R, &'a [X], E> {
+    Recognize::new(p, Arc::new(move |from, to| &source[from..to]))
+}
+
+// Label replaces what the error of p expects with label, as <?> of Haskell Parsec. It only
+// touches errors p produced without consuming input, deeper errors are more helpful as is. That
+// counts the errors p dropped when it succeeded without consuming, see relabel_dropped.
+pub struct Label<'a, T, R, E=SimpleError> {
+    parsec: Arc<Parsec<T, R, E>+'a>,
+    label: Arc<String>,
+}
+
+impl<'a, T, R, E> Label<'a, T, R, E> {
+    pub fn new(p:Arc<Parsec<T, R, E>+'a>, label:String) -> Label<'a, T, R, E> {
+        Label{parsec:p.clone(), label:Arc::new(label)}
+    }
+}
+
+impl<'a, T, R, E> Parsec<T, R, E> for Label<'a, T, R, E> where E:'static+Error+From<ParseError> {
+    fn parse(&self, state:&mut State<T>)->Status<R, E> {
+        self.reply(state).status()
+    }
+    fn reply(&self, state:&mut State<T>)->Reply<R, E> {
+        let before = state.aux().farthest.clone();
+        match self.parsec.reply(state) {
+            Reply::Empty(Err(err)) => {
+                rewrite_farthest(state, before, Some(err.pos()), |farthest| farthest.relabel(self.label.as_str()));
+                relabel_dropped::<T, E>(state, None);
+                Reply::Empty(Err(err.relabel(self.label.as_str())))
+            },
+            Reply::Empty(Ok(re)) => {
+                relabel_dropped::<T, E>(state, Some(self.label.as_str()));
+                Reply::Empty(Ok(re))
+            },
+            reply => reply,
+        }
+    }
+}
+
+impl<'a, 'b, T, R, E> FnOnce<(&'b mut State<T>, )> for Label<'a, T, R, E> where E:'static+Error+From<ParseError> {
+    type Output = Status<R, E>;
+    extern "rust-call" fn call_once(self, _: (&'b mut State<T>, )) -> Status<R, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, R, E> FnMut<(&'b mut State<T>, )> for Label<'a, T, R, E> where E:'static+Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'b mut State<T>, )) -> Status<R, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, R, E> Fn<(&'b mut State<T>, )> for Label<'a, T, R, E> where E:'static+Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'b mut State<T>, )) -> Status<R, E> {
+        let (state, ) = args;
+        self.parse(state)
+    }
+}
+
+impl<'a, T, R, E> Clone for Label<'a, T, R, E> {
+    fn clone(&self)->Self {
+        Label{parsec:self.parsec.clone(), label:self.label.clone()}
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.parsec = source.parsec.clone();
+        self.label = source.label.clone();
+    }
+}
+
+impl<'a, T, R, E> Debug for Label<'a, T, R, E> {
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        write!(formatter, "<label parsec: {}>", self.label)
+    }
+}
+
+impl<'a, T:'a+Clone, R:'a+Clone, E:'static+Error+From<ParseError>> M<'a, T, R, E> for Label<'a, T, R, E>{}
+
+pub fn label<'a, T, R, E>(p:Arc<Parsec<T, R, E>+'a>, label:String)->Label<'a, T, R, E> {
+    Label::new(p, label)
+}
+
+// Context pushes frame onto any error of p passing through it, so errors carry a breadcrumb
+// of the enclosing grammar rules, as "in object > in key 'servers': expected ','".
+pub struct Context<'a, T, R, E=SimpleError> {
+    parsec: Arc<Parsec<T, R, E>+'a>,
+    frame: Arc<String>,
+}
+
+impl<'a, T, R, E> Context<'a, T, R, E> {
+    pub fn new(p:Arc<Parsec<T, R, E>+'a>, frame:String) -> Context<'a, T, R, E> {
+        Context{parsec:p.clone(), frame:Arc::new(frame)}
+    }
+}
+
+impl<'a, T, R, E> Parsec<T, R, E> for Context<'a, T, R, E> where E:Error+From<ParseError> {
+    fn parse(&self, state:&mut State<T>)->Status<R, E> {
+        self.reply(state).status()
+    }
+    fn reply(&self, state:&mut State<T>)->Reply<R, E> {
+        let before = state.aux().farthest.clone();
+        let reply = self.parsec.reply(state);
+        let consumed = reply.is_consumed();
+        match reply.status() {
+            Err(err) => {
+                // a failure p backtracked over is inside the frame too
+                rewrite_farthest(state, before, None, |farthest| farthest.with_context(self.frame.as_str()));
+                Reply::new(consumed, Err(err.with_context(self.frame.as_str())))
+            },
+            ok => Reply::new(consumed, ok),
+        }
+    }
+}
+
+impl<'a, 'b, T, R, E> FnOnce<(&'b mut State<T>, )> for Context<'a, T, R, E> where E:Error+From<ParseError> {
+    type Output = Status<R, E>;
+    extern "rust-call" fn call_once(self, _: (&'b mut State<T>, )) -> Status<R, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, R, E> FnMut<(&'b mut State<T>, )> for Context<'a, T, R, E> where E:Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'b mut State<T>, )) -> Status<R, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, R, E> Fn<(&'b mut State<T>, )> for Context<'a, T, R, E> where E:Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'b mut State<T>, )) -> Status<R, E> {
+        let (state, ) = args;
+        self.parse(state)
+    }
+}
+
+impl<'a, T, R, E> Clone for Context<'a, T, R, E> {
+    fn clone(&self)->Self {
+        Context{parsec:self.parsec.clone(), frame:self.frame.clone()}
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.parsec = source.parsec.clone();
+        self.frame = source.frame.clone();
+    }
+}
+
+impl<'a, T, R, E> Debug for Context<'a, T, R, E> {
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        write!(formatter, "<context parsec: {}>", self.frame)
+    }
+}
+
+impl<'a, T:'a+Clone, R:'a+Clone, E:'static+Error+From<ParseError>> M<'a, T, R, E> for Context<'a, T, R, E>{}
+
+pub fn context<'a, T, R, E>(p:Arc<Parsec<T, R, E>+'a>, frame:String)->Context<'a, T, R, E> {
+    Context::new(p, frame)
+}
+
+// Cut commits to p: any error of p becomes fatal, try, either, many, skip_many and sep_by never
+// backtrack over it, so it fails the whole parse where it happened. It works as the cut of
+// Prolog, put it after the part which decides the alternative, as eq('[').then(cut(elements)).
+// SimpleError and ParseError carry the flag, a custom error type needs fatal and is_fatal.
+pub struct Cut<'a, T, R, E=SimpleError> {
+    parsec: Arc<Parsec<T, R, E>+'a>,
+}
+
+impl<'a, T, R, E> Cut<'a, T, R, E> {
+    pub fn new(p:Arc<Parsec<T, R, E>+'a>) -> Cut<'a, T, R, E> {
+        Cut{parsec:p.clone()}
+    }
+}
+
+impl<'a, T, R, E> Parsec<T, R, E> for Cut<'a, T, R, E> where E:Error+From<ParseError> {
+    fn parse(&self, state:&mut State<T>)->Status<R, E> {
+        self.reply(state).status()
+    }
+    fn reply(&self, state:&mut State<T>)->Reply<R, E> {
+        let before = state.aux().farthest.clone();
+        match self.parsec.reply(state) {
+            Reply::Consumed(Err(err)) | Reply::Empty(Err(err)) => {
+                rewrite_farthest(state, before, Some(err.pos()), |farthest| farthest.fatal());
+                Reply::Consumed(Err(err.fatal()))
+            },
+            reply => reply,
+        }
+    }
+}
+
+impl<'a, 'b, T, R, E> FnOnce<(&'b mut State<T>, )> for Cut<'a, T, R, E> where E:Error+From<ParseError> {
+    type Output = Status<R, E>;
+    extern "rust-call" fn call_once(self, _: (&'b mut State<T>, )) -> Status<R, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, R, E> FnMut<(&'b mut State<T>, )> for Cut<'a, T, R, E> where E:Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'b mut State<T>, )) -> Status<R, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, R, E> Fn<(&'b mut State<T>, )> for Cut<'a, T, R, E> where E:Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'b mut State<T>, )) -> Status<R, E> {
+        let (state, ) = args;
+        self.parse(state)
+    }
+}
+
+impl<'a, T, R, E> Clone for Cut<'a, T, R, E> {
+    fn clone(&self)->Self {
+        Cut{parsec:self.parsec.clone()}
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.parsec = source.parsec.clone();
+    }
+}
+
+impl<'a, T, R, E> Debug for Cut<'a, T, R, E> {
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        write!(formatter, "<cut parsec>")
+    }
+}
+
+impl<'a, T:'a+Clone, R:'a+Clone, E:'static+Error+From<ParseError>> M<'a, T, R, E> for Cut<'a, T, R, E>{}
+
+pub fn cut<'a, T, R, E>(p:Arc<Parsec<T, R, E>+'a>)->Cut<'a, T, R, E> {
+    Cut::new(p)
+}
+
+// Optional returns None instead of failing when p fails without consuming, as optionMaybe of
+// Haskell Parsec. Errors after p consumed input still fail it, the error of p failing without
+// consuming goes to drop_error.
+pub struct Optional<'a, T, R, E=SimpleError> {
+    parsec: Arc<Parsec<T, R, E>+'a>,
+}
+
+impl<'a, T, R, E> Optional<'a, T, R, E> {
+    pub fn new(p:Arc<Parsec<T, R, E>+'a>) -> Optional<'a, T, R, E> {
+        Optional{parsec:p.clone()}
+    }
+}
+
+impl<'a, T, R, E> Parsec<T, Option<R>, E> for Optional<'a, T, R, E> where E:'static+Error+From<ParseError> {
+    fn parse(&self, state:&mut State<T>)->Status<Option<R>, E> {
+        self.reply(state).status()
+    }
+    fn reply(&self, state:&mut State<T>)->Reply<Option<R>, E> {
+        let checkpoint = state.checkpoint();
+        match self.parsec.reply(state) {
+            Reply::Empty(Err(err)) => {
+                if err.is_fatal() {
+                    return Reply::Empty(Err(err));
+                }
+                match state.rollback(checkpoint) {
+                    Ok(_) => {
+                        drop_error(state, err);
+                        Reply::Empty(Ok(None))
+                    },
+                    Err(err) => Reply::Empty(Err(E::from(err))),
+                }
+            },
+            reply => reply.map(Some),
+        }
+    }
+}
+
+impl<'a, 'b, T, R, E> FnOnce<(&'b mut State<T>, )> for Optional<'a, T, R, E> where E:'static+Error+From<ParseError> {
+    type Output = Status<Option<R>, E>;
+    extern "rust-call" fn call_once(self, _: (&'b mut State<T>, )) -> Status<Option<R>, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, R, E> FnMut<(&'b mut State<T>, )> for Optional<'a, T, R, E> where E:'static+Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'b mut State<T>, )) -> Status<Option<R>, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, R, E> Fn<(&'b mut State<T>, )> for Optional<'a, T, R, E> where E:'static+Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'b mut State<T>, )) -> Status<Option<R>, E> {
+        let (state, ) = args;
+        self.parse(state)
+    }
+}
+
+impl<'a, T, R, E> Clone for Optional<'a, T, R, E> {
+    fn clone(&self)->Self {
+        Optional{parsec:self.parsec.clone()}
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.parsec = source.parsec.clone();
+    }
+}
+
+impl<'a, T, R, E> Debug for Optional<'a, T, R, E> {
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        write!(formatter, "<optional parsec>")
+    }
+}
+
+impl<'a, T:'a+Clone, R:'a+Clone, E:'static+Error+From<ParseError>> M<'a, T, Option<R>, E> for Optional<'a, T, R, E>{}
+
+pub fn optional<'a, T, R, E>(p:Arc<Parsec<T, R, E>+'a>)->Optional<'a, T, R, E> {
+    Optional::new(p)
+}
+
+// p, or default if p fails without consuming.
+pub fn option<'a, T:'a, R:'a, E:'static>(default:R, p:Arc<Parsec<T, R, E>+'a>)->Monad<'a, T, Option<R>, R, E>
+where T:Clone, R:Clone, E:Error+From<ParseError> {
+    monad(Arc::new(optional(p))).bind(Arc::new(Box::new(move |_:&mut State<T>, x:Option<R>| {
+        Ok(x.unwrap_or(default.clone()))
+    })))
+}
+
+pub fn optional_skip<'a, T:'a, R:'a, E:'static>(p:Arc<Parsec<T, R, E>+'a>)->Monad<'a, T, Option<R>, (), E>
+where T:Clone, R:Clone, E:Error+From<ParseError> {
+    monad(Arc::new(optional(p))).bind(Arc::new(Box::new(|_:&mut State<T>, _:Option<R>| Ok(()))))
+}
+
+// LookAhead parses p and returns its value without consuming anything, the state goes back to
+// where it was, user state included. If p fails, the error is kept as p replied it, as lookAhead
+// of Haskell's Parsec does: a p failing after consuming leaves the state where it failed and
+// the reply Consumed, so alternatives are not tried. Wrap p with try to make a failed look
+// ahead backtrack.
+pub struct LookAhead<'a, T, R, E=SimpleError> {
+    parsec: Arc<Parsec<T, R, E>+'a>,
+}
+
+impl<'a, T, R, E> LookAhead<'a, T, R, E> {
+    pub fn new(p:Arc<Parsec<T, R, E>+'a>) -> LookAhead<'a, T, R, E> {
+        LookAhead{parsec:p.clone()}
+    }
+}
+
+impl<'a, T, R, E> Parsec<T, R, E> for LookAhead<'a, T, R, E> where E:Error+From<ParseError> {
+    fn parse(&self, state:&mut State<T>)->Status<R, E> {
+        self.reply(state).status()
+    }
+    fn reply(&self, state:&mut State<T>)->Reply<R, E> {
+        let checkpoint = state.checkpoint();
+        match self.parsec.reply(state) {
+            Reply::Consumed(Ok(x)) | Reply::Empty(Ok(x)) => match state.rollback(checkpoint) {
+                Ok(_) => Reply::Empty(Ok(x)),
+                Err(err) => Reply::Consumed(Err(E::from(err))),
+            },
+            reply => reply,
+        }
+    }
+}
+
+impl<'a, 'b, T, R, E> FnOnce<(&'b mut State<T>, )> for LookAhead<'a, T, R, E> where E:Error+From<ParseError> {
+    type Output = Status<R, E>;
+    extern "rust-call" fn call_once(self, _: (&'b mut State<T>, )) -> Status<R, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, R, E> FnMut<(&'b mut State<T>, )> for LookAhead<'a, T, R, E> where E:Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'b mut State<T>, )) -> Status<R, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, R, E> Fn<(&'b mut State<T>, )> for LookAhead<'a, T, R, E> where E:Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'b mut State<T>, )) -> Status<R, E> {
+        let (state, ) = args;
+        self.parse(state)
+    }
+}
+
+impl<'a, T, R, E> Clone for LookAhead<'a, T, R, E> {
+    fn clone(&self)->Self {
+        LookAhead{parsec:self.parsec.clone()}
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.parsec = source.parsec.clone();
+    }
+}
+
+impl<'a, T, R, E> Debug for LookAhead<'a, T, R, E> {
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        write!(formatter, "<look ahead parsec>")
+    }
+}
+
+impl<'a, T:'a+Clone, R:'a+Clone, E:'static+Error+From<ParseError>> M<'a, T, R, E> for LookAhead<'a, T, R, E>{}
+
+pub fn look_ahead<'a, T, R, E>(p:Arc<Parsec<T, R, E>+'a>)->LookAhead<'a, T, R, E> {
+    LookAhead::new(p)
+}
+
+// NotFollowedBy succeeds only when p fails, and never consumes input, so keyword("if") can be
+// eq('i').then(eq('f')).over(not_followed_by(alpha)) to refuse "iffy". When p matches, the error
+// is unexpected what p parsed, built by Error::unexpected. A failure to read the input is not
+// taken as p failing, neither is a fatal one. What p failing leaves in the farthest failure and
+// the dropped errors is forgotten, as that failure is the success here.
+pub struct NotFollowedBy<'a, T, R, E=SimpleError> {
+    parsec: Arc<Parsec<T, R, E>+'a>,
+}
+
+impl<'a, T, R, E> NotFollowedBy<'a, T, R, E> {
+    pub fn new(p:Arc<Parsec<T, R, E>+'a>) -> NotFollowedBy<'a, T, R, E> {
+        NotFollowedBy{parsec:p.clone()}
+    }
+}
+
+impl<'a, T, R, E> Parsec<T, (), E> for NotFollowedBy<'a, T, R, E> where R:Debug, E:Error+From<ParseError> {
+    fn parse(&self, state:&mut State<T>)->Status<(), E> {
+        self.reply(state).status()
+    }
+    fn reply(&self, state:&mut State<T>)->Reply<(), E> {
+        let checkpoint = state.checkpoint();
+        let farthest = state.aux().farthest.clone();
+        let mut dropped = mem::replace(&mut state.aux_mut().dropped, Vec::new());
+        // positions are only looked up when p matched, which is the failure
+        let found = match self.parsec.reply(state) {
+            Reply::Consumed(Ok(x)) | Reply::Empty(Ok(x)) => Ok((x, state.source_pos())),
+            Reply::Consumed(Err(err)) | Reply::Empty(Err(err)) => if err.is_fatal() {
+                dropped.append(&mut state.aux_mut().dropped);
+                state.aux_mut().dropped = dropped;
+                return Reply::Consumed(Err(err));
+            } else {
+                Err(state.input_error())
+            },
+        };
+        {
+            let aux = state.aux_mut();
+            aux.farthest = farthest;
+            aux.dropped = dropped;
+        }
+        if let Err(err) = state.rollback(checkpoint) {
+            return Reply::Consumed(Err(E::from(err)));
+        }
+        match found {
+            Ok((x, end)) => {
+                let span = Span::new(state.source_pos(), end);
+                let unexpected = format!("{:?}", x);
+                state.failure(ParseError::unexpected_in(span, unexpected.clone()));
+                Reply::Empty(Err(E::unexpected(span, unexpected)))
+            },
+            Err(Some(err)) => Reply::Empty(Err(E::from(err))),
+            Err(None) => Reply::Empty(Ok(())),
+        }
+    }
+}
+
+impl<'a, 'b, T, R, E> FnOnce<(&'b mut State<T>, )> for NotFollowedBy<'a, T, R, E> where R:Debug, E:Error+From<ParseError> {
+    type Output = Status<(), E>;
+    extern "rust-call" fn call_once(self, _: (&'b mut State<T>, )) -> Status<(), E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, R, E> FnMut<(&'b mut State<T>, )> for NotFollowedBy<'a, T, R, E> where R:Debug, E:Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'b mut State<T>, )) -> Status<(), E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, R, E> Fn<(&'b mut State<T>, )> for NotFollowedBy<'a, T, R, E> where R:Debug, E:Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'b mut State<T>, )) -> Status<(), E> {
+        let (state, ) = args;
+        self.parse(state)
+    }
+}
+
+impl<'a, T, R, E> Clone for NotFollowedBy<'a, T, R, E> {
+    fn clone(&self)->Self {
+        NotFollowedBy{parsec:self.parsec.clone()}
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.parsec = source.parsec.clone();
+    }
+}
+
+impl<'a, T, R, E> Debug for NotFollowedBy<'a, T, R, E> {
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        write!(formatter, "<not followed by parsec>")
+    }
+}
+
+impl<'a, T:'a+Clone, R:'a+Debug, E:'static+Error+From<ParseError>> M<'a, T, (), E> for NotFollowedBy<'a, T, R, E>{}
+
+pub fn not_followed_by<'a, T, R, E>(p:Arc<Parsec<T, R, E>+'a>)->NotFollowedBy<'a, T, R, E> {
+    NotFollowedBy::new(p)
+}
+
+// Chain parses one or more p separated by op, and folds the values with the functions op
+// returns, as chainl1 and chainr1 of Haskell Parsec. It stops before an op failing without
+// consuming, or before an op matching nothing, as juxtaposition, when no p follows it, and
+// passes that error to drop_error. An op which consumed input must be followed by p.
+pub struct Chain<'a, T, R, E=SimpleError> {
+    parsec: Arc<Parsec<T, R, E>+'a>,
+    op: Arc<Parsec<T, Binary<'a, R>, E>+'a>,
+    right: bool,
+}
+
+impl<'a, T, R, E> Chain<'a, T, R, E> {
+    pub fn new(p:Arc<Parsec<T, R, E>+'a>, op:Arc<Parsec<T, Binary<'a, R>, E>+'a>, right:bool) -> Chain<'a, T, R, E> {
+        Chain{parsec:p.clone(), op:op.clone(), right:right}
+    }
+}
+
+impl<'a, T, R, E> Parsec<T, R, E> for Chain<'a, T, R, E> where E:'static+Error+From<ParseError> {
+    fn parse(&self, state:&mut State<T>)->Status<R, E> {
+        self.reply(state).status()
+    }
+    fn reply(&self, state:&mut State<T>)->Reply<R, E> {
+        let first = self.parsec.reply(state);
+        let mut consumed = first.is_consumed();
+        let mut xs = match first.status() {
+            Ok(x) => vec![x],
+            Err(err) => return Reply::new(consumed, Err(err)),
+        };
+        let mut fs:Vec<Binary<'a, R>> = Vec::new();
+        loop {
+            let checkpoint = state.checkpoint();
+            let op_consumed;
+            match self.op.reply(state) {
+                Reply::Empty(Err(err)) => {
+                    if err.is_fatal() {
+                        return Reply::new(consumed, Err(err));
+                    }
+                    if let Err(err) = state.rollback(checkpoint) {
+                        return Reply::new(consumed, Err(E::from(err)));
+                    }
+                    drop_error(state, err);
+                    break;
+                },
+                reply => {
+                    op_consumed = reply.is_consumed();
+                    consumed = consumed || op_consumed;
+                    match reply.status() {
+                        Ok(f) => fs.push(f),
+                        Err(err) => return Reply::new(consumed, Err(err)),
+                    }
+                },
+            }
+            let y = match self.parsec.reply(state) {
+                Reply::Empty(Err(err)) if !op_consumed && !err.is_fatal() => {
+                    if let Err(err) = state.rollback(checkpoint) {
+                        return Reply::new(consumed, Err(E::from(err)));
+                    }
+                    drop_error(state, err);
+                    fs.pop();
+                    break;
+                },
+                next => {
+                    consumed = consumed || next.is_consumed();
+                    match next.status() {
+                        Ok(y) => y,
+                        Err(err) => return Reply::new(consumed, Err(err)),
+                    }
+                },
+            };
+            // fold left chains as they come, right ones have to wait for the last operand
+            if self.right {
+                xs.push(y);
+            } else {
+                let x = xs.pop().unwrap();
+                xs.push(fs.pop().unwrap()(x, y));
+            }
+        }
+        let mut re = xs.pop().unwrap();
+        while let Some(f) = fs.pop() {
+            re = f(xs.pop().unwrap(), re);
+        }
+        Reply::new(consumed, Ok(re))
+    }
+}
+
+impl<'a, 'b, T, R, E> FnOnce<(&'b mut State<T>, )> for Chain<'a, T, R, E> where E:'static+Error+From<ParseError> {
+    type Output = Status<R, E>;
+    extern "rust-call" fn call_once(self, _: (&'b mut State<T>, )) -> Status<R, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, R, E> FnMut<(&'b mut State<T>, )> for Chain<'a, T, R, E> where E:'static+Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'b mut State<T>, )) -> Status<R, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, R, E> Fn<(&'b mut State<T>, )> for Chain<'a, T, R, E> where E:'static+Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'b mut State<T>, )) -> Status<R, E> {
+        let (state, ) = args;
+        self.parse(state)
+    }
+}
+
+impl<'a, T, R, E> Clone for Chain<'a, T, R, E> {
+    fn clone(&self)->Self {
+        Chain{parsec:self.parsec.clone(), op:self.op.clone(), right:self.right}
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.parsec = source.parsec.clone();
+        self.op = source.op.clone();
+        self.right = source.right;
+    }
+}
+
+impl<'a, T, R, E> Debug for Chain<'a, T, R, E> {
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        write!(formatter, "<chain{} parsec>", if self.right { "r1" } else { "l1" })
+    }
+}
+
+impl<'a, T:'a+Clone, R:'a+Clone, E:'static+Error+From<ParseError>> M<'a, T, R, E> for Chain<'a, T, R, E>{}
+
+pub fn chainl1<'a, T, R, E>(p:Arc<Parsec<T, R, E>+'a>, op:Arc<Parsec<T, Binary<'a, R>, E>+'a>)->Chain<'a, T, R, E> {
+    Chain::new(p, op, false)
+}
+
+pub fn chainr1<'a, T, R, E>(p:Arc<Parsec<T, R, E>+'a>, op:Arc<Parsec<T, Binary<'a, R>, E>+'a>)->Chain<'a, T, R, E> {
+    Chain::new(p, op, true)
+}
+
+pub mod pratt;
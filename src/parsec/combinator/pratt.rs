@@ -0,0 +1,265 @@
+use parsec::{State, Error, ParseError, SimpleError, Parsec, Status, Reply, Monad, M};
+use parsec::expr::Unary;
+use std::sync::{Arc, Weak, Mutex, MutexGuard};
+use std::fmt::{Debug, Formatter};
+use std::fmt;
+
+// Handlers registered so far, shared by every copy of the parser.
+struct Rules<'a, T, R, E> {
+    nuds: Vec<Arc<Parsec<T, R, E>+'a>>,
+    leds: Vec<(u32, Arc<Parsec<T, Unary<'a, R>, E>+'a>)>,
+}
+
+impl<'a, T, R, E> Clone for Rules<'a, T, R, E> {
+    fn clone(&self)->Self {
+        Rules{nuds:self.nuds.clone(), leds:self.leds.clone()}
+    }
+}
+
+// A parse takes the rules as they are when it starts, registering handlers makes new rules.
+type Shared<'a, T, R, E> = Mutex<Arc<Rules<'a, T, R, E>>>;
+
+fn lock<'s, 'a, T, R, E>(rules:&'s Shared<'a, T, R, E>)->MutexGuard<'s, Arc<Rules<'a, T, R, E>>> {
+    // the lock is never held over user code, no panic can poison it
+    rules.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// Pratt is a top down operator precedence parser. Handlers are registered at runtime, a nud
+// parses an expression from its first token, as a literal, a prefix operator or a group, and a
+// led continues the expression on its left, returning the function which builds the bigger
+// one, as an infix or postfix operator, an index or a call. A led is only tried when its left
+// binding power is above the minimum power of the expression being parsed, and must consume
+// input when it matches.
+//
+// Handlers parse their operands with an Operand of the parser, min_power(bp) gives one which
+// stops before weaker operators. Operands don't keep the handlers alive, so handlers holding
+// them make no reference cycle, but they fail once every copy of the Pratt is dropped.
+// Handlers registered while the parser is parsing, as by a handler, are used by the operands
+// parsed after that, the expressions already being parsed go on with the handlers they began
+// with.
+pub struct Pratt<'a, T, R, E=SimpleError> {
+    rules: Arc<Shared<'a, T, R, E>>,
+}
+
+impl<'a, T, R, E> Pratt<'a, T, R, E> {
+    pub fn new() -> Pratt<'a, T, R, E> {
+        Pratt{rules:Arc::new(Mutex::new(Arc::new(Rules{nuds:Vec::new(), leds:Vec::new()})))}
+    }
+
+    // The parser for operands which only take operators binding tighter than power.
+    pub fn min_power(&self, power:u32) -> Operand<'a, T, R, E> {
+        Operand{rules:Arc::downgrade(&self.rules), power:power}
+    }
+
+    pub fn nud(&self, handler:Arc<Parsec<T, R, E>+'a>) {
+        let mut rules = lock(&self.rules);
+        let mut next = (**rules).clone();
+        next.nuds.push(handler);
+        *rules = Arc::new(next);
+    }
+
+    pub fn led(&self, power:u32, handler:Arc<Parsec<T, Unary<'a, R>, E>+'a>) {
+        let mut rules = lock(&self.rules);
+        let mut next = (**rules).clone();
+        next.leds.push((power, handler));
+        *rules = Arc::new(next);
+    }
+}
+
+// Handlers for plain operators, op only matches the symbol.
+impl<'a, T:'a+Clone, R:'a+Clone, E:'static+Error+From<ParseError>> Pratt<'a, T, R, E> {
+    // Prefix operator, its operand binds operators above power.
+    pub fn prefix<S:'a, F>(&self, op:Arc<Parsec<T, S, E>+'a>, power:u32, f:F) where F:'a+Fn(R)->R {
+        let operand = self.min_power(power);
+        let f = Arc::new(f);
+        self.nud(Arc::new(Monad::new(op, Arc::new(Box::new(move |state:&mut State<T>, _:S| {
+            operand.parse(state).map(|x| f(x))
+        })))));
+    }
+
+    // Infix operator of left binding power left and right binding power right, left < right
+    // makes it left associative and left > right right associative.
+    pub fn infix<S:'a, F>(&self, op:Arc<Parsec<T, S, E>+'a>, left:u32, right:u32, f:F)
+    where F:'a+Fn(R, R)->R {
+        let operand = self.min_power(right);
+        let f = Arc::new(f);
+        self.led(left, Arc::new(Monad::new(op, Arc::new(Box::new(move |state:&mut State<T>, _:S| {
+            let y = try!(operand.parse(state));
+            let f = f.clone();
+            let g:Unary<'a, R> = Arc::new(Box::new(move |x| f(x, y.clone())));
+            Ok(g)
+        })))));
+    }
+
+    pub fn postfix<S:'a, F>(&self, op:Arc<Parsec<T, S, E>+'a>, power:u32, f:F) where F:'a+Fn(R)->R {
+        let f:Unary<'a, R> = Arc::new(Box::new(f));
+        self.led(power, Arc::new(Monad::new(op, Arc::new(Box::new(move |_:&mut State<T>, _:S| Ok(f.clone()))))));
+    }
+}
+
+// Expression above power. The lock is only held to take the rules, handlers parse operands
+// with them again.
+fn parse_rules<'a, T, R, E>(rules:&Shared<'a, T, R, E>, power:u32, state:&mut State<T>)->Status<R, E>
+where E:Error+From<ParseError> {
+    let rules = lock(rules).clone();
+    let mut error:Option<E> = None;
+    let mut left = None;
+    for nud in rules.nuds.iter() {
+        let checkpoint = state.checkpoint();
+        match nud.reply(state) {
+            Reply::Empty(Err(err)) => {
+                if err.is_fatal() {
+                    return Err(err);
+                }
+                try!(state.rollback(checkpoint));
+                // no nud fits, the error says what all of them expect
+                error = Some(match error {
+                    Some(error) => error.merge(err),
+                    None => err,
+                });
+            },
+            reply => {
+                left = Some(try!(reply.status()));
+                break;
+            },
+        }
+    }
+    let mut left = match (left, error) {
+        (Some(left), _) => left,
+        (None, Some(err)) => return Err(err),
+        (None, None) => {
+            let err = ParseError::at(state.source_pos(), String::from("no prefix handler registered"));
+            return Err(E::from(state.failure(err)));
+        },
+    };
+
+    'operators: loop {
+        for &(_, ref led) in rules.leds.iter().filter(|&&(bp, _)| bp > power) {
+            let checkpoint = state.checkpoint();
+            match led.reply(state) {
+                Reply::Empty(Err(err)) => {
+                    if err.is_fatal() {
+                        return Err(err);
+                    }
+                    try!(state.rollback(checkpoint));
+                },
+                Reply::Empty(Ok(_)) => {
+                    // it would match again at the same place forever, as many's parser
+                    let message = String::from("pratt led handler accepts empty input");
+                    return Err(E::from(state.failure(ParseError::at(state.source_pos(), message))));
+                },
+                reply => {
+                    let f = try!(reply.status());
+                    left = f(left);
+                    continue 'operators;
+                },
+            }
+        }
+        return Ok(left);
+    }
+}
+
+impl<'a, T, R, E> Parsec<T, R, E> for Pratt<'a, T, R, E> where E:Error+From<ParseError> {
+    fn parse(&self, state:&mut State<T>)->Status<R, E> {
+        parse_rules(&self.rules, 0, state)
+    }
+}
+
+impl<'a, 'b, T, R, E> FnOnce<(&'b mut State<T>, )> for Pratt<'a, T, R, E> where E:Error+From<ParseError> {
+    type Output = Status<R, E>;
+    extern "rust-call" fn call_once(self, _: (&'b mut State<T>, )) -> Status<R, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, R, E> FnMut<(&'b mut State<T>, )> for Pratt<'a, T, R, E> where E:Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'b mut State<T>, )) -> Status<R, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, R, E> Fn<(&'b mut State<T>, )> for Pratt<'a, T, R, E> where E:Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'b mut State<T>, )) -> Status<R, E> {
+        let (state, ) = args;
+        self.parse(state)
+    }
+}
+
+impl<'a, T, R, E> Clone for Pratt<'a, T, R, E> {
+    fn clone(&self)->Self {
+        Pratt{rules:self.rules.clone()}
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.rules = source.rules.clone();
+    }
+}
+
+impl<'a, T, R, E> Debug for Pratt<'a, T, R, E> {
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        write!(formatter, "<pratt parsec>")
+    }
+}
+
+impl<'a, T:'a+Clone, R:'a+Clone, E:'static+Error+From<ParseError>> M<'a, T, R, E> for Pratt<'a, T, R, E>{}
+
+pub fn pratt<'a, T, R, E>()->Pratt<'a, T, R, E> {
+    Pratt::new()
+}
+
+// Operand is the Pratt parser as its handlers see it, min_power of Pratt makes one.
+pub struct Operand<'a, T, R, E=SimpleError> {
+    rules: Weak<Shared<'a, T, R, E>>,
+    power: u32,
+}
+
+impl<'a, T, R, E> Parsec<T, R, E> for Operand<'a, T, R, E> where E:Error+From<ParseError> {
+    fn parse(&self, state:&mut State<T>)->Status<R, E> {
+        match self.rules.upgrade() {
+            Some(rules) => parse_rules(&rules, self.power, state),
+            None => {
+                let err = ParseError::at(state.source_pos(), String::from("pratt parser is dropped"));
+                Err(E::from(state.failure(err)))
+            },
+        }
+    }
+}
+
+impl<'a, 'b, T, R, E> FnOnce<(&'b mut State<T>, )> for Operand<'a, T, R, E> where E:Error+From<ParseError> {
+    type Output = Status<R, E>;
+    extern "rust-call" fn call_once(self, _: (&'b mut State<T>, )) -> Status<R, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, R, E> FnMut<(&'b mut State<T>, )> for Operand<'a, T, R, E> where E:Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'b mut State<T>, )) -> Status<R, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, R, E> Fn<(&'b mut State<T>, )> for Operand<'a, T, R, E> where E:Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'b mut State<T>, )) -> Status<R, E> {
+        let (state, ) = args;
+        self.parse(state)
+    }
+}
+
+impl<'a, T, R, E> Clone for Operand<'a, T, R, E> {
+    fn clone(&self)->Self {
+        Operand{rules:self.rules.clone(), power:self.power}
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.rules = source.rules.clone();
+        self.power = source.power;
+    }
+}
+
+impl<'a, T, R, E> Debug for Operand<'a, T, R, E> {
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        write!(formatter, "<pratt operand above {}>", self.power)
+    }
+}
+
+impl<'a, T:'a+Clone, R:'a+Clone, E:'static+Error+From<ParseError>> M<'a, T, R, E> for Operand<'a, T, R, E>{}
@@ -0,0 +1,187 @@
+use parsec::{Error, ParseError, SourcePos};
+
+// Position in the shape of Language Server Protocol, line and character start from 0, and
+// character counts UTF-16 code units as LSP clients do by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    line: usize,
+    character: usize,
+}
+
+impl Position {
+    pub fn new(line:usize, character:usize)->Position {
+        Position{line:line, character:character}
+    }
+    // Position of the byte offset in text, "\n", "\r\n" and a lone "\r" all break lines.
+    pub fn of(text:&str, offset:usize)->Position {
+        let mut offset = if offset > text.len() { text.len() } else { offset };
+        while !text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let mut line = 0;
+        let mut character = 0;
+        let mut chars = text[..offset].chars().peekable();
+        while let Some(c) = chars.next() {
+            if c == '\n' || (c == '\r' && chars.peek() != Some(&'\n')) {
+                line += 1;
+                character = 0;
+            } else if c != '\r' {
+                character += c.len_utf16();
+            }
+        }
+        Position{line:line, character:character}
+    }
+    pub fn line(&self)->usize {
+        self.line
+    }
+    pub fn character(&self)->usize {
+        self.character
+    }
+    fn to_json(&self)->String {
+        format!("{{\"line\":{},\"character\":{}}}", self.line, self.character)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Range {
+    start: Position,
+    end: Position,
+}
+
+impl Range {
+    pub fn new(start:Position, end:Position)->Range {
+        Range{start:start, end:end}
+    }
+    pub fn start(&self)->Position {
+        self.start
+    }
+    pub fn end(&self)->Position {
+        self.end
+    }
+    fn to_json(&self)->String {
+        format!("{{\"start\":{},\"end\":{}}}", self.start.to_json(), self.end.to_json())
+    }
+}
+
+// DiagnosticSeverity of LSP, the values are the protocol's numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error = 1,
+    Warning = 2,
+    Information = 3,
+    Hint = 4,
+}
+
+// Diagnostic is a parse error in the shape of LSP's Diagnostic. The unexpected item, expected
+// set and context stack of a ParseError go to the free form `data` field in JSON.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    range: Range,
+    severity: Severity,
+    source: String,
+    message: String,
+    unexpected: Option<String>,
+    expected: Vec<String>,
+    contexts: Vec<String>,
+}
+
+impl Diagnostic {
+    // Diagnostic of any error, text is the input the parser read.
+    pub fn new(text:&str, err:&Error)->Diagnostic {
+        let span = err.span();
+        Diagnostic{
+            range: Range::new(position(text, span.start()), position(text, span.end())),
+            severity: Severity::Error,
+            source: String::from("ruskell"),
+            message: err.message(),
+            unexpected: None,
+            expected: Vec::new(),
+            contexts: Vec::new(),
+        }
+    }
+
+    pub fn from_parse_error(text:&str, err:&ParseError)->Diagnostic {
+        let mut re = Diagnostic::new(text, err);
+        re.unexpected = err.unexpected().map(String::from);
+        re.expected = err.expected().to_vec();
+        re.contexts = err.contexts().to_vec();
+        re
+    }
+
+    pub fn with_severity(mut self, severity:Severity)->Diagnostic {
+        self.severity = severity;
+        self
+    }
+
+    // Name of the tool in the `source` field, "ruskell" by default.
+    pub fn with_source(mut self, source:&str)->Diagnostic {
+        self.source = String::from(source);
+        self
+    }
+
+    pub fn range(&self)->Range {
+        self.range
+    }
+    pub fn severity(&self)->Severity {
+        self.severity
+    }
+    pub fn source(&self)->&str {
+        &self.source
+    }
+    pub fn message(&self)->&str {
+        &self.message
+    }
+    pub fn unexpected(&self)->Option<&str> {
+        self.unexpected.as_ref().map(|x| x.as_str())
+    }
+    pub fn expected(&self)->&[String] {
+        &self.expected
+    }
+    pub fn contexts(&self)->&[String] {
+        &self.contexts
+    }
+
+    pub fn to_json(&self)->String {
+        let unexpected = match self.unexpected {
+            Some(ref unexpected) => json_string(unexpected),
+            None => String::from("null"),
+        };
+        format!("{{\"range\":{},\"severity\":{},\"source\":{},\"message\":{},\
+                 \"data\":{{\"unexpected\":{},\"expected\":{},\"contexts\":{}}}}}",
+                self.range.to_json(), self.severity as usize, json_string(&self.source),
+                json_string(&self.message), unexpected, json_strings(&self.expected),
+                json_strings(&self.contexts))
+    }
+}
+
+// JSON array of diagnostics, as the `diagnostics` of a publishDiagnostics notification.
+pub fn to_json(diagnostics:&[Diagnostic])->String {
+    let items:Vec<String> = diagnostics.iter().map(|d| d.to_json()).collect();
+    format!("[{}]", items.join(","))
+}
+
+fn position(text:&str, pos:SourcePos)->Position {
+    Position::of(text, pos.offset_in(text))
+}
+
+fn json_string(text:&str)->String {
+    let mut re = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => re.push_str("\\\""),
+            '\\' => re.push_str("\\\\"),
+            '\n' => re.push_str("\\n"),
+            '\r' => re.push_str("\\r"),
+            '\t' => re.push_str("\\t"),
+            c if (c as u32) < 0x20 => re.push_str(&format!("\\u{:04x}", c as u32)),
+            c => re.push(c),
+        }
+    }
+    re.push('"');
+    re
+}
+
+fn json_strings(items:&[String])->String {
+    let items:Vec<String> = items.iter().map(|x| json_string(x)).collect();
+    format!("[{}]", items.join(","))
+}
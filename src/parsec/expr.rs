@@ -0,0 +1,269 @@
+use parsec::{State, Error, ParseError, SimpleError, Parsec, Status, Reply, Monad, M};
+use std::sync::Arc;
+use std::fmt::{Debug, Formatter};
+use std::fmt;
+
+// Functions the operator parsers return, applied to the operands.
+pub type Unary<'a, R> = Arc<Box<Fn(R)->R+'a>>;
+pub type Binary<'a, R> = Arc<Box<Fn(R, R)->R+'a>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Assoc {
+    Left,
+    Right,
+    None,
+}
+
+impl Assoc {
+    fn name(&self)->&'static str {
+        match *self {
+            Assoc::Left => "left",
+            Assoc::Right => "right",
+            Assoc::None => "non",
+        }
+    }
+}
+
+pub enum Operator<'a, T, R, E=SimpleError> {
+    Infix(Arc<Parsec<T, Binary<'a, R>, E>+'a>, Assoc),
+    Prefix(Arc<Parsec<T, Unary<'a, R>, E>+'a>),
+    Postfix(Arc<Parsec<T, Unary<'a, R>, E>+'a>),
+}
+
+// Operators whose parser only matches the symbol, the function is fixed.
+impl<'a, T:'a+Clone, R:'a, E:'static+Error> Operator<'a, T, R, E> {
+    pub fn infix<S:'a, F>(op:Arc<Parsec<T, S, E>+'a>, f:F, assoc:Assoc)->Operator<'a, T, R, E>
+    where F:'a+Fn(R, R)->R {
+        let f:Binary<'a, R> = Arc::new(Box::new(f));
+        Operator::Infix(Arc::new(Monad::new(op, Arc::new(Box::new(move |_:&mut State<T>, _:S| Ok(f.clone()))))),
+                        assoc)
+    }
+    pub fn prefix<S:'a, F>(op:Arc<Parsec<T, S, E>+'a>, f:F)->Operator<'a, T, R, E> where F:'a+Fn(R)->R {
+        let f:Unary<'a, R> = Arc::new(Box::new(f));
+        Operator::Prefix(Arc::new(Monad::new(op, Arc::new(Box::new(move |_:&mut State<T>, _:S| Ok(f.clone()))))))
+    }
+    pub fn postfix<S:'a, F>(op:Arc<Parsec<T, S, E>+'a>, f:F)->Operator<'a, T, R, E> where F:'a+Fn(R)->R {
+        let f:Unary<'a, R> = Arc::new(Box::new(f));
+        Operator::Postfix(Arc::new(Monad::new(op, Arc::new(Box::new(move |_:&mut State<T>, _:S| Ok(f.clone()))))))
+    }
+}
+
+impl<'a, T, R, E> Clone for Operator<'a, T, R, E> {
+    fn clone(&self)->Self {
+        match *self {
+            Operator::Infix(ref p, assoc) => Operator::Infix(p.clone(), assoc),
+            Operator::Prefix(ref p) => Operator::Prefix(p.clone()),
+            Operator::Postfix(ref p) => Operator::Postfix(p.clone()),
+        }
+    }
+}
+
+impl<'a, T, R, E> Debug for Operator<'a, T, R, E> {
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        match *self {
+            Operator::Infix(_, assoc) => write!(formatter, "<infix {} operator>", assoc.name()),
+            Operator::Prefix(_) => write!(formatter, "<prefix operator>"),
+            Operator::Postfix(_) => write!(formatter, "<postfix operator>"),
+        }
+    }
+}
+
+// Operators of one precedence level, grouped by kind.
+struct Level<'a, T, R, E> {
+    prefix: Vec<Arc<Parsec<T, Unary<'a, R>, E>+'a>>,
+    postfix: Vec<Arc<Parsec<T, Unary<'a, R>, E>+'a>>,
+    left: Vec<Arc<Parsec<T, Binary<'a, R>, E>+'a>>,
+    right: Vec<Arc<Parsec<T, Binary<'a, R>, E>+'a>>,
+    none: Vec<Arc<Parsec<T, Binary<'a, R>, E>+'a>>,
+}
+
+impl<'a, T, R, E> Level<'a, T, R, E> {
+    fn new(operators:Vec<Operator<'a, T, R, E>>)->Level<'a, T, R, E> {
+        let mut level = Level{prefix:Vec::new(), postfix:Vec::new(), left:Vec::new(),
+                              right:Vec::new(), none:Vec::new()};
+        for operator in operators {
+            match operator {
+                Operator::Infix(p, Assoc::Left) => level.left.push(p),
+                Operator::Infix(p, Assoc::Right) => level.right.push(p),
+                Operator::Infix(p, Assoc::None) => level.none.push(p),
+                Operator::Prefix(p) => level.prefix.push(p),
+                Operator::Postfix(p) => level.postfix.push(p),
+            }
+        }
+        level
+    }
+
+    fn infix(&self, assoc:Assoc)->&[Arc<Parsec<T, Binary<'a, R>, E>+'a>] {
+        match assoc {
+            Assoc::Left => &self.left,
+            Assoc::Right => &self.right,
+            Assoc::None => &self.none,
+        }
+    }
+}
+
+// Expression is buildExpressionParser of Haskell's Parsec. The table lists precedence levels
+// from the tightest binding to the loosest, each level's operators parse into the functions
+// building the value. Every operand takes at most one prefix and one postfix operator. Mixing
+// operators of different associativity in one level without parentheses, or chaining non
+// associative ones, is an ambiguity error.
+pub struct Expression<'a, T, R, E=SimpleError> {
+    levels: Arc<Vec<Level<'a, T, R, E>>>,
+    term: Arc<Parsec<T, R, E>+'a>,
+}
+
+impl<'a, T, R, E> Expression<'a, T, R, E> {
+    pub fn new(table:Vec<Vec<Operator<'a, T, R, E>>>, term:Arc<Parsec<T, R, E>+'a>)->Expression<'a, T, R, E> {
+        let levels = table.into_iter().map(Level::new).collect();
+        Expression{levels:Arc::new(levels), term:term.clone()}
+    }
+}
+
+impl<'a, T, R, E> Expression<'a, T, R, E> where E:Error+From<ParseError> {
+    // Expression with the operators of the first n levels.
+    fn level(&self, n:usize, state:&mut State<T>)->Status<R, E> {
+        if n == 0 {
+            return self.term.parse(state);
+        }
+        let ops = &self.levels[n-1];
+        let x = try!(self.operand(n, state));
+        if let Some(f) = try!(choose(&ops.right, state)) {
+            let mut xs = vec![x];
+            let mut fs = vec![f];
+            loop {
+                xs.push(try!(self.operand(n, state)));
+                match try!(choose(&ops.right, state)) {
+                    Some(f) => fs.push(f),
+                    None => break,
+                }
+            }
+            try!(self.ambiguous(ops, Assoc::Right, state));
+            let mut re = xs.pop().unwrap();
+            while let Some(f) = fs.pop() {
+                re = f(xs.pop().unwrap(), re);
+            }
+            return Ok(re);
+        }
+        if let Some(f) = try!(choose(&ops.left, state)) {
+            let mut re = x;
+            let mut f = f;
+            loop {
+                let y = try!(self.operand(n, state));
+                re = f(re, y);
+                match try!(choose(&ops.left, state)) {
+                    Some(g) => f = g,
+                    None => break,
+                }
+            }
+            try!(self.ambiguous(ops, Assoc::Left, state));
+            return Ok(re);
+        }
+        if let Some(f) = try!(choose(&ops.none, state)) {
+            let y = try!(self.operand(n, state));
+            try!(self.ambiguous(ops, Assoc::None, state));
+            return Ok(f(x, y));
+        }
+        Ok(x)
+    }
+
+    // Operand of level n, an expression of the tighter levels with its prefix and postfix.
+    fn operand(&self, n:usize, state:&mut State<T>)->Status<R, E> {
+        let ops = &self.levels[n-1];
+        let prefix = try!(choose(&ops.prefix, state));
+        let x = try!(self.level(n-1, state));
+        let x = match prefix {
+            Some(f) => f(x),
+            None => x,
+        };
+        match try!(choose(&ops.postfix, state)) {
+            Some(f) => Ok(f(x)),
+            None => Ok(x),
+        }
+    }
+
+    // After a chain of assoc operators, an infix operator of the same level may only follow
+    // when it chains the same way.
+    fn ambiguous(&self, ops:&Level<'a, T, R, E>, assoc:Assoc, state:&mut State<T>)->Status<(), E> {
+        for other in [Assoc::Left, Assoc::Right, Assoc::None].iter() {
+            if *other == assoc && assoc != Assoc::None {
+                continue;
+            }
+            let checkpoint = state.checkpoint();
+            if try!(choose(ops.infix(*other), state)).is_some() {
+                // the error points at the operator, look its position up only now
+                try!(state.rollback(checkpoint));
+                let message = format!("ambiguous use of a {} associative operator", other.name());
+                return Err(E::from(state.failure(ParseError::at(state.source_pos(), message))));
+            }
+        }
+        Ok(())
+    }
+}
+
+// The first operator parses, None if all of them fail without consuming.
+fn choose<'a, T, F, E>(ops:&[Arc<Parsec<T, F, E>+'a>], state:&mut State<T>)->Status<Option<F>, E>
+where E:Error+From<ParseError> {
+    for op in ops {
+        let checkpoint = state.checkpoint();
+        match op.reply(state) {
+            Reply::Empty(Err(err)) => {
+                if err.is_fatal() {
+                    return Err(err);
+                }
+                try!(state.rollback(checkpoint));
+            },
+            reply => return reply.status().map(Some),
+        }
+    }
+    Ok(None)
+}
+
+impl<'a, T, R, E> Parsec<T, R, E> for Expression<'a, T, R, E> where E:Error+From<ParseError> {
+    fn parse(&self, state:&mut State<T>)->Status<R, E> {
+        self.level(self.levels.len(), state)
+    }
+}
+
+impl<'a, 'b, T, R, E> FnOnce<(&'b mut State<T>, )> for Expression<'a, T, R, E> where E:Error+From<ParseError> {
+    type Output = Status<R, E>;
+    extern "rust-call" fn call_once(self, _: (&'b mut State<T>, )) -> Status<R, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, R, E> FnMut<(&'b mut State<T>, )> for Expression<'a, T, R, E> where E:Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'b mut State<T>, )) -> Status<R, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, R, E> Fn<(&'b mut State<T>, )> for Expression<'a, T, R, E> where E:Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'b mut State<T>, )) -> Status<R, E> {
+        let (state, ) = args;
+        self.parse(state)
+    }
+}
+
+impl<'a, T, R, E> Clone for Expression<'a, T, R, E> {
+    fn clone(&self)->Self {
+        Expression{levels:self.levels.clone(), term:self.term.clone()}
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.levels = source.levels.clone();
+        self.term = source.term.clone();
+    }
+}
+
+impl<'a, T, R, E> Debug for Expression<'a, T, R, E> {
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        write!(formatter, "<expression parsec with {} levels>", self.levels.len())
+    }
+}
+
+impl<'a, T:'a+Clone, R:'a+Clone, E:'static+Error+From<ParseError>> M<'a, T, R, E> for Expression<'a, T, R, E>{}
+
+pub fn expression<'a, T, R, E>(table:Vec<Vec<Operator<'a, T, R, E>>>, term:Arc<Parsec<T, R, E>+'a>)
+    ->Expression<'a, T, R, E> {
+    Expression::new(table, term)
+}
@@ -5,10 +5,14 @@ use std::boxed::Box;
 use std::fmt::{Debug, Formatter};
 use std::fmt;
 use std::clone::Clone;
+use std::any::Any;
+use std::mem;
+use parsec::combinator::{Label, Context, Cut, Optional, option, optional_skip};
 
 pub struct VecState<T> {
     index : usize,
     buffer: Vec<T>,
+    aux: Aux,
 }
 
 impl<A> FromIterator<A> for VecState<A> {
@@ -16,15 +20,269 @@ impl<A> FromIterator<A> for VecState<A> {
         VecState{
             index:0,
             buffer:Vec::from_iter(iterator.into_iter()),
+            aux:Aux::default(),
         }
     }
 }
 
+// Position of a token in the source. index is the token index State::pos uses, offset the byte
+// offset of the token in the original text, None for states which don't know it, as VecState.
+// line and column start from 1, states which know nothing about lines, as VecState, report 0
+// for both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourcePos {
+    index: usize,
+    offset: Option<usize>,
+    line: usize,
+    column: usize,
+}
+
+impl SourcePos {
+    pub fn new(index:usize, offset:usize, line:usize, column:usize)->SourcePos {
+        SourcePos{index:index, offset:Some(offset), line:line, column:column}
+    }
+    // A position without line information, for flat token sequences.
+    pub fn at(index:usize)->SourcePos {
+        SourcePos{index:index, offset:None, line:0, column:0}
+    }
+    pub fn index(&self)->usize {
+        self.index
+    }
+    pub fn offset(&self)->Option<usize> {
+        self.offset
+    }
+    // Byte offset in text, a position without one is taken as the index of a char in it.
+    pub fn offset_in(&self, text:&str)->usize {
+        match self.offset {
+            Some(offset) => offset,
+            None => text.char_indices().nth(self.index).map_or(text.len(), |(offset, _)| offset),
+        }
+    }
+    pub fn line(&self)->usize {
+        self.line
+    }
+    pub fn column(&self)->usize {
+        self.column
+    }
+    pub fn has_line(&self)->bool {
+        self.line > 0
+    }
+    // Same place in the text at another index, for states which count tokens over it.
+    fn with_index(self, index:usize)->SourcePos {
+        SourcePos{index:index, ..self}
+    }
+}
+
+impl fmt::Display for SourcePos {
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        if self.has_line() {
+            write!(formatter, "line {}, column {}", self.line, self.column)
+        } else {
+            write!(formatter, "{}", self.index)
+        }
+    }
+}
+
+// Range of source a token covers, from start until end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    start: SourcePos,
+    end: SourcePos,
+}
+
+impl Span {
+    pub fn new(start:SourcePos, end:SourcePos)->Span {
+        Span{start:start, end:end}
+    }
+    pub fn start(&self)->SourcePos {
+        self.start
+    }
+    pub fn end(&self)->SourcePos {
+        self.end
+    }
+}
+
+// Auxiliary state every State carries beside its position. Try and Either keep what a
+// checkpoint needs to restore it when they backtrack.
+#[derive(Default)]
+pub struct Aux {
+    // user state, same as the `u` of Haskell Parsec's ParsecT s u m a
+    user: Option<Arc<Any>>,
+    // errors recovered by recover_with, and how many of them to collect before giving up
+    errors: Vec<Arc<Any>>,
+    error_limit: Option<usize>,
+    // farthest failure any parser met, rollback keeps it
+    farthest: Option<Arc<ParseError>>,
+    // errors drop_error kept, with the state position they were dropped at
+    dropped: Vec<(usize, Box<Any>)>,
+}
+
+// Opaque snapshot of a State, taken by State::checkpoint and restored by State::rollback.
+#[derive(Clone)]
+pub struct Checkpoint {
+    pos: usize,
+    user: Option<Arc<Any>>,
+    // errors are only pushed, rollback truncates them back to this length
+    errors: usize,
+    error_limit: Option<usize>,
+    // dropped errors are only pushed too
+    dropped: usize,
+}
+
 pub trait State<T> {
     fn pos(&self)-> usize;
+    fn source_pos(&self)->SourcePos {
+        SourcePos::at(self.pos())
+    }
+    // Span of the token at current position, empty at eof or if the state don't know.
+    fn span(&self)->Span {
+        Span::new(self.source_pos(), self.source_pos())
+    }
+    // Low level position move used by rollback, parsers should use checkpoint/rollback.
     fn seek_to(&mut self, usize)->bool;
+    fn checkpoint(&self)->Checkpoint {
+        let aux = self.aux();
+        Checkpoint{pos:self.pos(), user:aux.user.clone(), errors:aux.errors.len(), error_limit:aux.error_limit,
+                   dropped:aux.dropped.len()}
+    }
+    fn rollback(&mut self, checkpoint:Checkpoint)->Status<(), ParseError> {
+        let pos = checkpoint.pos;
+        {
+            let aux = self.aux_mut();
+            aux.user = checkpoint.user;
+            aux.errors.truncate(checkpoint.errors);
+            aux.error_limit = checkpoint.error_limit;
+            // errors dropped in the branch given up can't meet any error again
+            aux.dropped.truncate(checkpoint.dropped);
+        }
+        if self.seek_to(pos) {
+            Ok(())
+        } else {
+            let message = format!("can not rollback to {}, it is behind the committed point", pos);
+            Err(ParseError::at(self.source_pos(), message))
+        }
+    }
     fn next(&mut self)->Option<T>;
-    fn next_by(&mut self, &Fn(&T)->bool)->Status<T>;
+    fn next_by(&mut self, &Fn(&T)->bool)->Status<T, ParseError>;
+    // Item next() would return, without moving. A reader state may have to read it first.
+    fn peek(&mut self)->Option<T>;
+    // Error in the input itself, as broken encoding or io failure, which stops the state
+    // before the real end. next() returns None at there too.
+    fn input_error(&self)->Option<ParseError> {
+        None
+    }
+    // Error for next() returned None.
+    fn eof_error(&self)->ParseError {
+        self.input_error().unwrap_or(ParseError::unexpected_at(self.source_pos(), String::from("end of input")))
+    }
+    fn aux(&self)->&Aux;
+    fn aux_mut(&mut self)->&mut Aux;
+    // Atoms pass their errors through here, so the state knows the farthest position any of
+    // them failed at and what was expected there, even after try backtracked over it.
+    fn failure(&mut self, err:ParseError)->ParseError {
+        let farthest = match self.aux_mut().farthest.take() {
+            // most failures are behind the farthest one, they leave it as it is
+            Some(farthest) => if farthest.pos.index() > err.pos.index() {
+                farthest
+            } else if farthest.pos.index() == err.pos.index() {
+                let farthest = Arc::try_unwrap(farthest).unwrap_or_else(|shared| (*shared).clone());
+                Arc::new(farthest.merge(err.clone()))
+            } else {
+                Arc::new(err.clone())
+            },
+            None => Arc::new(err.clone()),
+        };
+        self.aux_mut().farthest = Some(farthest);
+        err
+    }
+    fn farthest_failure(&self)->Option<ParseError> {
+        self.aux().farthest.as_ref().map(|err| (**err).clone())
+    }
+    fn user_state(&self)->Option<Arc<Any>> {
+        self.aux().user.clone()
+    }
+    fn set_user_state(&mut self, user:Option<Arc<Any>>) {
+        self.aux_mut().user = user;
+    }
+}
+
+// Combinators which succeed after p failed without consuming, as optional, many_m_n, sep_end_by
+// and chainl1, pass p's error here after their rollback. Haskell's Parsec keeps that error in
+// the empty ok reply and merges it into the next error at the same place, so "expected digit
+// or ';'" lists both. Replies here carry no error on success, so the state keeps it instead,
+// with the position it was dropped at: once any parser consumed input the error is stale, and
+// a rollback forgets errors dropped after its checkpoint. Bind merges what is still there
+// into the error its binder fails with. An error from farther on, as one behind a try, does not
+// say what was expected here and is not kept.
+fn drop_error<T, E>(state:&mut State<T>, err:E) where E:'static+Error {
+    let pos = state.pos();
+    if err.pos() != pos {
+        return;
+    }
+    let aux = state.aux_mut();
+    aux.dropped.retain(|&(at, _)| at == pos);
+    aux.dropped.push((pos, Box::new(err)));
+}
+
+fn merge_dropped<T, E>(state:&mut State<T>, err:E)->E where E:'static+Error {
+    let pos = state.pos();
+    let dropped = mem::replace(&mut state.aux_mut().dropped, Vec::new());
+    let mut re:Option<E> = None;
+    for (at, dropped) in dropped {
+        if at != pos {
+            continue;
+        }
+        if let Ok(dropped) = dropped.downcast::<E>() {
+            re = Some(match re {
+                Some(re) => re.merge(*dropped),
+                None => *dropped,
+            });
+        }
+    }
+    match re {
+        Some(re) => re.merge(err),
+        None => err,
+    }
+}
+
+// Label speaks for what p expected at the position p started from. If p succeeded there
+// without consuming, the errors p dropped become one error expecting the label. If p failed
+// there, its error is relabeled already and they are forgotten.
+fn relabel_dropped<T, E>(state:&mut State<T>, label:Option<&str>) where E:'static+Error {
+    let pos = state.pos();
+    let dropped = mem::replace(&mut state.aux_mut().dropped, Vec::new());
+    let mut re:Option<E> = None;
+    for (at, dropped) in dropped {
+        if at != pos {
+            continue;
+        }
+        if let Ok(dropped) = dropped.downcast::<E>() {
+            re = Some(match re {
+                Some(re) => re.merge(*dropped),
+                None => *dropped,
+            });
+        }
+    }
+    if let (Some(re), Some(label)) = (re, label) {
+        state.aux_mut().dropped.push((pos, Box::new(re.relabel(label))));
+    }
+}
+
+// Label, context and cut rewrite the error of p on its way out, the farthest failure gets the
+// same rewrite when p met it, that is when it changed since before. With at, only a farthest
+// failure at that position is rewritten.
+fn rewrite_farthest<T, F>(state:&mut State<T>, before:Option<Arc<ParseError>>, at:Option<usize>, f:F)
+where F:FnOnce(ParseError)->ParseError {
+    let farthest = match (state.aux_mut().farthest.take(), before) {
+        (Some(farthest), Some(ref before)) if Arc::ptr_eq(&farthest, before) => farthest,
+        (Some(farthest), _) => if at.map_or(true, |at| at == farthest.pos.index()) {
+            Arc::new(f((*farthest).clone()))
+        } else {
+            farthest
+        },
+        (None, _) => return,
+    };
+    state.aux_mut().farthest = Some(farthest);
 }
 
 impl<T> State<T> for VecState<T> where T:Clone {
@@ -32,7 +290,7 @@ impl<T> State<T> for VecState<T> where T:Clone {
         self.index
     }
     fn seek_to(&mut self, to:usize) -> bool {
-        if 0 as usize <= to && to < self.buffer.len() {
+        if to <= self.buffer.len() {
             self.index = to;
             true
         } else {
@@ -48,69 +306,374 @@ impl<T> State<T> for VecState<T> where T:Clone {
             None
         }
     }
-    fn next_by(&mut self, pred:&Fn(&T)->bool)->Status<T>{
+    fn next_by(&mut self, pred:&Fn(&T)->bool)->Status<T, ParseError>{
         if 0 as usize <= self.index && self.index < self.buffer.len() {
             let ref item = self.buffer[self.index];
             if pred(item) {
                 self.index += 1;
                 Ok(item.clone())
             } else {
-                Err(SimpleError::new(self.index, String::from("predicate failed")))
+                Err(ParseError::at(self.source_pos(), String::from("predicate failed")))
             }
         } else {
-            Err(SimpleError::new(self.index, String::from("eof")))
+            Err(self.eof_error())
         }
     }
+    fn peek(&mut self)->Option<T>{
+        self.buffer.get(self.index).cloned()
+    }
+    fn aux(&self)->&Aux {
+        &self.aux
+    }
+    fn aux_mut(&mut self)->&mut Aux {
+        &mut self.aux
+    }
 }
 
-#[derive(Debug, Clone)]
+// Default error of parsers, the position and the message. Labels replace the message, contexts
+// prefix it and cut makes it fatal, as they do to ParseError. Use ParseError as the error type
+// to get expectations merged between alternatives.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SimpleError {
-    _pos: usize,
+    _pos: SourcePos,
     _message: String,
-
+    // frames of context combinators the error passed through, the outermost first
+    _contexts: Vec<String>,
+    _fatal: bool,
 }
 
 impl SimpleError {
     pub fn new(pos:usize, message:String)->SimpleError{
+        SimpleError::at(SourcePos::at(pos), message)
+    }
+    pub fn at(pos:SourcePos, message:String)->SimpleError{
         SimpleError{
             _pos: pos,
             _message: message,
+            _contexts: Vec::new(),
+            _fatal: false,
         }
     }
 }
 
+// Errors parsers could fail with. Parsers are generic over it, and build their own failures
+// as ParseError, so an error type also needs From<ParseError> to be used by them.
 pub trait Error {
     fn pos(&self)->usize;
-    fn message(&self)->&str;
+    fn source_pos(&self)->SourcePos;
+    fn message(&self)->String;
+    fn span(&self)->Span {
+        Span::new(self.source_pos(), self.source_pos())
+    }
+    // Combine errors of two alternatives both failed without consuming, the farther one wins.
+    fn merge(self, other:Self)->Self where Self:Sized {
+        if self.pos() > other.pos() {
+            self
+        } else {
+            other
+        }
+    }
+    // Replace what the error says is expected, errors know nothing about it just ignore.
+    fn relabel(self, _:&str)->Self where Self:Sized {
+        self
+    }
+    // Push an enclosing frame, as "in object", onto the error passing through a context.
+    fn with_context(self, _:&str)->Self where Self:Sized {
+        self
+    }
+    // Add a message telling more about the failure, as how many items a repeat found.
+    fn with_message(self, _:&str)->Self where Self:Sized {
+        self
+    }
+    // Fatal errors come from a cut, no combinator backtracks over them. Error types which
+    // can't carry the flag are never fatal, so cut has no effect on them.
+    fn is_fatal(&self)->bool {
+        false
+    }
+    fn fatal(self)->Self where Self:Sized {
+        self
+    }
+    // Error for an item found where nothing of it may be, as not_followed_by refuses. Error types
+    // with a typed case for it override this, the rest convert the ParseError.
+    fn unexpected(span:Span, item:String)->Self where Self:Sized+From<ParseError> {
+        Self::from(ParseError::unexpected_in(span, item))
+    }
 }
 
 impl Error for SimpleError {
     fn pos(&self)->usize {
+        self._pos.index()
+    }
+    fn source_pos(&self)->SourcePos {
         self._pos
     }
-    fn message(&self)->&str {
-        self._message.as_str()
+    // "in object > in key 'servers': expected ','"
+    fn message(&self)->String {
+        if self._contexts.is_empty() {
+            self._message.clone()
+        } else {
+            format!("{}: {}", self._contexts.join(" > "), self._message)
+        }
+    }
+    // Nothing tells what the message expected, so the later alternative stands for both.
+    fn merge(self, other:SimpleError)->SimpleError {
+        if self.pos() > other.pos() {
+            self
+        } else if self.pos() < other.pos() {
+            other
+        } else {
+            let mut re = other;
+            if re._contexts != self._contexts {
+                re._contexts.clear();
+            }
+            re._fatal = re._fatal || self._fatal;
+            re
+        }
+    }
+    // Nothing but the message tells what was expected, so the label replaces all of it.
+    fn relabel(mut self, label:&str)->SimpleError {
+        if !label.is_empty() {
+            self._message = format!("expected {}", label);
+        }
+        self
+    }
+    fn with_context(mut self, frame:&str)->SimpleError {
+        self._contexts.insert(0, String::from(frame));
+        self
+    }
+    fn is_fatal(&self)->bool {
+        self._fatal
+    }
+    fn fatal(mut self)->SimpleError {
+        self._fatal = true;
+        self
+    }
+    fn with_message(mut self, message:&str)->SimpleError {
+        self._message = if self._message.is_empty() {
+            String::from(message)
+        } else {
+            format!("{}, {}", self._message, message)
+        };
+        self
+    }
+}
+
+// Structured error as Haskell Parsec's: where parsing failed, the unexpected item found there
+// and the items expected there. Errors of alternatives failed at the same position merge,
+// so the message reads "unexpected 'x', expected digit, '-' or '('".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pos: SourcePos,
+    // end of the failing span, same as pos if the failure has no width
+    end: SourcePos,
+    unexpected: Option<String>,
+    expected: Vec<String>,
+    messages: Vec<String>,
+    // frames of context combinators the error passed through, the outermost first
+    contexts: Vec<String>,
+    fatal: bool,
+}
+
+impl ParseError {
+    pub fn new(pos:usize, message:String)->ParseError {
+        ParseError::at(SourcePos::at(pos), message)
+    }
+    pub fn at(pos:SourcePos, message:String)->ParseError {
+        ParseError{pos:pos, end:pos, unexpected:None, expected:Vec::new(), messages:vec![message],
+                   contexts:Vec::new(), fatal:false}
+    }
+    pub fn unexpected_at(pos:SourcePos, unexpected:String)->ParseError {
+        ParseError::unexpected_in(Span::new(pos, pos), unexpected)
+    }
+    // Unexpected item covers the span.
+    pub fn unexpected_in(span:Span, unexpected:String)->ParseError {
+        ParseError{pos:span.start(), end:span.end(), unexpected:Some(unexpected),
+                   expected:Vec::new(), messages:Vec::new(), contexts:Vec::new(), fatal:false}
+    }
+    // Add an expected item, expected items are a set.
+    pub fn expect(mut self, item:String)->ParseError {
+        if !self.expected.contains(&item) {
+            self.expected.push(item);
+        }
+        self
+    }
+    pub fn unexpected(&self)->Option<&str> {
+        self.unexpected.as_ref().map(|x| x.as_str())
+    }
+    pub fn expected(&self)->&[String] {
+        &self.expected
+    }
+    pub fn messages(&self)->&[String] {
+        &self.messages
+    }
+    pub fn contexts(&self)->&[String] {
+        &self.contexts
+    }
+    pub fn with_context(mut self, frame:&str)->ParseError {
+        self.contexts.insert(0, String::from(frame));
+        self
+    }
+    pub fn with_message(mut self, message:&str)->ParseError {
+        if !self.messages.iter().any(|x| x == message) {
+            self.messages.push(String::from(message));
+        }
+        self
+    }
+    pub fn is_fatal(&self)->bool {
+        self.fatal
+    }
+    pub fn fatal(mut self)->ParseError {
+        self.fatal = true;
+        self
+    }
+    // Replace the expected items with label, an empty label just clears them.
+    pub fn relabel(mut self, label:&str)->ParseError {
+        self.expected.clear();
+        if label.is_empty() {
+            self
+        } else {
+            self.expect(String::from(label))
+        }
+    }
+    // Merge errors of two alternatives, the one failed farther wins, or union them if they
+    // failed at the same position. Frames of alternatives which both failed there tell rules
+    // neither of them got into, so contexts are kept only when the two agree.
+    pub fn merge(self, other:ParseError)->ParseError {
+        if self.pos.index() > other.pos.index() {
+            self
+        } else if self.pos.index() < other.pos.index() {
+            other
+        } else {
+            let mut re = self;
+            if re.unexpected.is_none() {
+                re.unexpected = other.unexpected;
+                re.end = other.end;
+            }
+            if re.contexts != other.contexts {
+                re.contexts.clear();
+            }
+            re.fatal = re.fatal || other.fatal;
+            for item in other.expected {
+                re = re.expect(item);
+            }
+            for message in other.messages {
+                if !re.messages.contains(&message) {
+                    re.messages.push(message);
+                }
+            }
+            re
+        }
+    }
+}
+
+// "a", "a or b", "a, b or c"
+fn or_list(items:&[String])->String {
+    match items.len() {
+        0 => String::new(),
+        1 => items[0].clone(),
+        n => format!("{} or {}", items[..n-1].join(", "), items[n-1]),
+    }
+}
+
+impl Error for ParseError {
+    fn pos(&self)->usize {
+        self.pos.index()
+    }
+    fn source_pos(&self)->SourcePos {
+        self.pos
+    }
+    fn span(&self)->Span {
+        Span::new(self.pos, self.end)
+    }
+    fn merge(self, other:ParseError)->ParseError {
+        ParseError::merge(self, other)
+    }
+    fn relabel(self, label:&str)->ParseError {
+        ParseError::relabel(self, label)
+    }
+    fn with_context(self, frame:&str)->ParseError {
+        ParseError::with_context(self, frame)
+    }
+    fn with_message(self, message:&str)->ParseError {
+        ParseError::with_message(self, message)
+    }
+    fn is_fatal(&self)->bool {
+        self.fatal
+    }
+    fn fatal(self)->ParseError {
+        ParseError::fatal(self)
+    }
+    // "in object > in key 'servers': unexpected '}', expected ','"
+    fn message(&self)->String {
+        let mut parts = Vec::new();
+        if let Some(ref unexpected) = self.unexpected {
+            parts.push(format!("unexpected {}", unexpected));
+        }
+        if !self.expected.is_empty() {
+            parts.push(format!("expected {}", or_list(&self.expected)));
+        }
+        parts.extend(self.messages.iter().cloned());
+        if self.contexts.is_empty() {
+            parts.join(", ")
+        } else {
+            format!("{}: {}", self.contexts.join(" > "), parts.join(", "))
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        write!(formatter, "{}: {}", self.pos, self.message())
+    }
+}
+
+impl fmt::Display for SimpleError {
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        write!(formatter, "{}: {}", self._pos, self.message())
+    }
+}
+
+impl From<SimpleError> for ParseError {
+    fn from(err:SimpleError)->ParseError {
+        ParseError{contexts:err._contexts, fatal:err._fatal, ..ParseError::at(err._pos, err._message)}
+    }
+}
+
+impl From<ParseError> for SimpleError {
+    fn from(mut err:ParseError)->SimpleError {
+        let contexts = mem::replace(&mut err.contexts, Vec::new());
+        SimpleError{_pos:err.pos, _message:err.message(), _contexts:contexts, _fatal:err.fatal}
     }
 }
 
 //pub trait Parsec<T:'static+Clone, R:'static+Clone>:Debug where Self:Parsec<T, R>+Clone+'static {
-pub trait Parsec<T, R>:Debug {
-    fn parse(&self, &mut State<T>)->Status<R>;
+pub trait Parsec<T, R, E=SimpleError>:Debug {
+    fn parse(&self, &mut State<T>)->Status<R, E>;
+    // Parse and tell whether input was consumed. Parsers which know it override this, the
+    // default compares positions before and after parse.
+    fn reply(&self, state:&mut State<T>)->Reply<R, E> {
+        let pos = state.pos();
+        let re = self.parse(state);
+        Reply::new(state.pos() != pos, re)
+    }
 }
 // TODO: move Generic Type Param P to bind/then/over function
 // Type Continuation(Result) Then Pass
-pub trait M<T:'static, R:'static>:Parsec<T, R> where Self:Clone+'static, T:Clone, R:Clone {
-    fn bind<P:'static+Clone>(self, binder:Arc<Box<Fn(&mut State<T>, R)->Status<P>>>)->Monad<T, R, P> {
+// Parsers live as long as 'a, the data they borrow, so results could borrow the source of a
+// StrState or SliceState. Parsers which borrow nothing fit any 'a.
+pub trait M<'a, T:'a, R:'a, E:'static=SimpleError>:Parsec<T, R, E>
+where Self:Clone+'a, T:Clone, R:Clone, E:Error+From<ParseError> {
+    fn bind<P:'a+Clone>(self, binder:Arc<Box<Fn(&mut State<T>, R)->Status<P, E>+'a>>)->Monad<'a, T, R, P, E> {
         Monad::new(Arc::new(self), binder.clone())
     }
-    fn then<P:'static+Clone>(self, then:Arc<Parsec<T, P>>)->Monad<T, R, P> {
+    fn then<P:'a+Clone>(self, then:Arc<Parsec<T, P, E>+'a>)->Monad<'a, T, R, P, E> {
         let then = then.clone();
         Monad::new(Arc::new(self), Arc::new(Box::new(move |state: &mut State<T>, _:R| {
             let then = then.clone();
             then.parse(state)
         })))
     }
-    fn over<P:'static+Clone>(self, over:Arc<Parsec<T, P>>)->Monad<T, R, R> {
+    fn over<P:'a+Clone>(self, over:Arc<Parsec<T, P, E>+'a>)->Monad<'a, T, R, R, E> {
         let over = over.clone();
         Monad::new(Arc::new(self), Arc::new(Box::new(move |state: &mut State<T>, x:R| {
             let over = over.clone();
@@ -122,61 +685,138 @@ pub trait M<T:'static, R:'static>:Parsec<T, R> where Self:Clone+'static, T:Clone
             }
         })))
     }
+    fn label(self, label:String)->Label<'a, T, R, E> {
+        Label::new(Arc::new(self), label)
+    }
+    fn context(self, frame:String)->Context<'a, T, R, E> {
+        Context::new(Arc::new(self), frame)
+    }
+    fn cut(self)->Cut<'a, T, R, E> {
+        Cut::new(Arc::new(self))
+    }
+    fn optional(self)->Optional<'a, T, R, E> {
+        Optional::new(Arc::new(self))
+    }
+    fn option(self, default:R)->Monad<'a, T, Option<R>, R, E> {
+        option(default, Arc::new(self))
+    }
+    fn optional_skip(self)->Monad<'a, T, Option<R>, (), E> {
+        optional_skip(Arc::new(self))
+    }
+}
+
+pub type Status<T, E=SimpleError> = Result<T, E>;
+
+// Reply of a parser as in the Parsec paper, its result and whether it consumed input to get
+// there. Either only tries the next alternative after an Empty error, Try turns a Consumed
+// error into an Empty one.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reply<T, E=SimpleError> {
+    Consumed(Status<T, E>),
+    Empty(Status<T, E>),
 }
 
-pub type Status<T> = Result<T, SimpleError>;
+impl<T, E> Reply<T, E> {
+    pub fn new(consumed:bool, re:Status<T, E>)->Reply<T, E> {
+        if consumed {
+            Reply::Consumed(re)
+        } else {
+            Reply::Empty(re)
+        }
+    }
+    // Reply of a parser reading a single token, which consumes it only on success.
+    pub fn token(re:Status<T, E>)->Reply<T, E> {
+        Reply::new(re.is_ok(), re)
+    }
+    pub fn map<U, F>(self, f:F)->Reply<U, E> where F:FnOnce(T)->U {
+        match self {
+            Reply::Consumed(re) => Reply::Consumed(re.map(f)),
+            Reply::Empty(re) => Reply::Empty(re.map(f)),
+        }
+    }
+    pub fn status(self)->Status<T, E> {
+        match self {
+            Reply::Consumed(re) => re,
+            Reply::Empty(re) => re,
+        }
+    }
+    pub fn is_consumed(&self)->bool {
+        match *self {
+            Reply::Consumed(_) => true,
+            Reply::Empty(_) => false,
+        }
+    }
+}
 
 // Type Continuation Then Pass
-pub struct Monad<T, C, P> {
-    parsec: Arc<Parsec<T, C>>,
-    binder: Arc<Box<Fn(&mut State<T>, C)->Status<P>>>,
+pub struct Monad<'a, T, C, P, E=SimpleError> {
+    parsec: Arc<Parsec<T, C, E>+'a>,
+    binder: Arc<Box<Fn(&mut State<T>, C)->Status<P, E>+'a>>,
 }
 
-impl<T:'static, C:'static, P:'static> Monad<T, C, P>
+impl<'a, T:'a, C:'a, P:'a, E:'static> Monad<'a, T, C, P, E>
 where T:Clone, P:Clone {
-    pub fn new(parsec: Arc<Parsec<T, C>>, binder: Arc<Box<Fn(&mut State<T>, C)->Status<P>>>)-> Monad<T, C, P> {
+    pub fn new(parsec: Arc<Parsec<T, C, E>+'a>, binder: Arc<Box<Fn(&mut State<T>, C)->Status<P, E>+'a>>)-> Monad<'a, T, C, P, E> {
         Monad{parsec:parsec.clone(), binder:binder.clone()}
     }
 }
 
-impl<T, C, P> Parsec<T, P> for Monad<T, C, P>
-where T:Clone, P:Clone {
-    fn parse(&self, state: &mut State<T>) -> Status<P> {
-        let x = self.parsec.parse(state);
-        if x.is_ok() {
-            let pre = x.unwrap();
-            let re = (self.binder.clone())(state, pre);
-            re
-        } else {
-            Err(x.err().unwrap())
+impl<'a, T, C, P, E> Monad<'a, T, C, P, E>
+where T:Clone, P:Clone, E:'static+Error {
+    // Run the binder, an error it fails with where p dropped one merges with it.
+    fn bind_pre(&self, state: &mut State<T>, pre:C) -> Status<P, E> {
+        let re = (self.binder.clone())(state, pre);
+        re.map_err(|err| merge_dropped(state, err))
+    }
+}
+
+impl<'a, T, C, P, E> Parsec<T, P, E> for Monad<'a, T, C, P, E>
+where T:Clone, P:Clone, E:'static+Error {
+    fn parse(&self, state: &mut State<T>) -> Status<P, E> {
+        match self.parsec.parse(state) {
+            Ok(pre) => self.bind_pre(state, pre),
+            Err(err) => Err(err),
+        }
+    }
+    fn reply(&self, state: &mut State<T>) -> Reply<P, E> {
+        match self.parsec.reply(state) {
+            Reply::Consumed(Ok(pre)) => Reply::Consumed(self.bind_pre(state, pre)),
+            Reply::Empty(Ok(pre)) => {
+                // the binder is a closure, only positions could tell what it did
+                let pos = state.pos();
+                let re = self.bind_pre(state, pre);
+                Reply::new(state.pos() != pos, re)
+            },
+            Reply::Consumed(Err(err)) => Reply::Consumed(Err(err)),
+            Reply::Empty(Err(err)) => Reply::Empty(Err(err)),
         }
     }
 }
 
-impl<'a, T, C, P> FnOnce<(&'a mut State<T>, )> for Monad<T, C, P>
-where T:Clone, P:Clone {
-    type Output = Status<P>;
-    extern "rust-call" fn call_once(self, _: (&'a mut State<T>, )) -> Status<P> {
+impl<'a, 'b, T, C, P, E> FnOnce<(&'b mut State<T>, )> for Monad<'a, T, C, P, E>
+where T:Clone, P:Clone, E:'static+Error {
+    type Output = Status<P, E>;
+    extern "rust-call" fn call_once(self, _: (&'b mut State<T>, )) -> Status<P, E> {
         panic!("Not implement!");
     }
 }
 
-impl<'a, T, C, P> FnMut<(&'a mut State<T>, )> for Monad<T, C, P>
-where T:Clone, P:Clone {
-    extern "rust-call" fn call_mut(&mut self, _: (&'a mut State<T>, )) -> Status<P> {
+impl<'a, 'b, T, C, P, E> FnMut<(&'b mut State<T>, )> for Monad<'a, T, C, P, E>
+where T:Clone, P:Clone, E:'static+Error {
+    extern "rust-call" fn call_mut(&mut self, _: (&'b mut State<T>, )) -> Status<P, E> {
         panic!("Not implement!");
     }
 }
 
-impl<'a, T, C, P> Fn<(&'a mut State<T>, )> for Monad<T, C, P>
-where T:Clone, P:Clone {
-    extern "rust-call" fn call(&self, args: (&'a mut State<T>, )) -> Status<P> {
+impl<'a, 'b, T, C, P, E> Fn<(&'b mut State<T>, )> for Monad<'a, T, C, P, E>
+where T:Clone, P:Clone, E:'static+Error {
+    extern "rust-call" fn call(&self, args: (&'b mut State<T>, )) -> Status<P, E> {
         let (state, ) = args;
         self.parse(state)
     }
 }
 
-impl<T, C, P> Clone for Monad<T, C, P>
+impl<'a, T, C, P, E> Clone for Monad<'a, T, C, P, E>
 where T:Clone, P:Clone {
     fn clone(&self)->Self {
         Monad{parsec:self.parsec.clone(), binder:self.binder.clone()}
@@ -188,57 +828,62 @@ where T:Clone, P:Clone {
     }
 }
 
-impl<T, C, P> Debug for Monad<T, C, P> where T:Clone, P:Clone{
+impl<'a, T, C, P, E> Debug for Monad<'a, T, C, P, E> where T:Clone, P:Clone{
     fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
         "<monad environment>".fmt(formatter)
     }
 }
 
-impl<T:'static, C:'static, P:'static> M<T, P> for Monad<T, C, P> where T:Clone, C:Clone, P:Clone {}
+impl<'a, T:'a, C:'a, P:'a, E:'static> M<'a, T, P, E> for Monad<'a, T, C, P, E>
+where T:Clone, C:Clone, P:Clone, E:Error+From<ParseError> {}
 
-pub fn monad<T:'static, R:'static>(parsec:Arc<Parsec<T, R>>)->Monad<T, R, R> where T:Clone, R:Clone {
+pub fn monad<'a, T:'a, R:'a, E:'static>(parsec:Arc<Parsec<T, R, E>+'a>)->Monad<'a, T, R, R, E>
+where T:Clone, R:Clone {
     Monad::new(parsec, Arc::new(Box::new(|_:&mut State<T>, re:R| Ok(re))))
 }
 
 // A monad just return parsec
-pub struct Parser<T, R> {
-    parsec: Arc<Parsec<T, R>>,
+pub struct Parser<'a, T, R, E=SimpleError> {
+    parsec: Arc<Parsec<T, R, E>+'a>,
 }
 
-impl<T:'static, R:'static> Parser<T, R>
+impl<'a, T:'a, R:'a, E:'static> Parser<'a, T, R, E>
 where T:Clone, R:Clone {
-    pub fn new(parsec: Arc<Parsec<T, R>> )-> Parser<T, R> {
+    pub fn new(parsec: Arc<Parsec<T, R, E>+'a> )-> Parser<'a, T, R, E> {
         Parser{parsec:parsec.clone()}
     }
 }
 
-impl<T, R> Parsec<T, R> for Parser<T, R> where T:Clone, R:Clone {
-    fn parse(&self, state: &mut State<T>) -> Status<R> {
+impl<'a, T, R, E> Parsec<T, R, E> for Parser<'a, T, R, E> where T:Clone, R:Clone {
+    fn parse(&self, state: &mut State<T>) -> Status<R, E> {
         self.parsec.parse(state)
     }
+    fn reply(&self, state: &mut State<T>) -> Reply<R, E> {
+        self.parsec.reply(state)
+    }
 }
 
-impl<'a, T, R> FnOnce<(&'a mut State<T>, )> for Parser<T, R> where T:Clone, R:Clone {
-    type Output = Status<R>;
-    extern "rust-call" fn call_once(self, _: (&'a mut State<T>, )) -> Status<R> {
+impl<'a, 'b, T, R, E> FnOnce<(&'b mut State<T>, )> for Parser<'a, T, R, E> where T:Clone, R:Clone {
+    type Output = Status<R, E>;
+    extern "rust-call" fn call_once(self, _: (&'b mut State<T>, )) -> Status<R, E> {
         panic!("Not implement!");
     }
 }
 
-impl<'a, T, R> FnMut<(&'a mut State<T>, )> for Parser<T, R> where T:Clone, R:Clone {
-    extern "rust-call" fn call_mut(&mut self, _: (&'a mut State<T>, )) -> Status<R> {
+impl<'a, 'b, T, R, E> FnMut<(&'b mut State<T>, )> for Parser<'a, T, R, E> where T:Clone, R:Clone {
+    extern "rust-call" fn call_mut(&mut self, _: (&'b mut State<T>, )) -> Status<R, E> {
         panic!("Not implement!");
     }
 }
 
-impl<'a, T, R> Fn<(&'a mut State<T>, )> for Parser<T, R> where T:Clone, R:Clone {
-    extern "rust-call" fn call(&self, args: (&'a mut State<T>, )) -> Status<R> {
+impl<'a, 'b, T, R, E> Fn<(&'b mut State<T>, )> for Parser<'a, T, R, E> where T:Clone, R:Clone {
+    extern "rust-call" fn call(&self, args: (&'b mut State<T>, )) -> Status<R, E> {
         let (state, ) = args;
         self.parse(state)
     }
 }
 
-impl<T, R> Clone for Parser<T, R> where T:Clone, R:Clone {
+impl<'a, T, R, E> Clone for Parser<'a, T, R, E> where T:Clone, R:Clone {
     fn clone(&self)->Self {
         Parser{parsec:self.parsec.clone()}
     }
@@ -248,59 +893,72 @@ impl<T, R> Clone for Parser<T, R> where T:Clone, R:Clone {
     }
 }
 
-impl<T, R> Debug for Parser<T, R> where T:Clone, R:Clone{
+impl<'a, T, R, E> Debug for Parser<'a, T, R, E> where T:Clone, R:Clone{
     fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
         "<parsec monad environment>".fmt(formatter)
     }
 }
 
-impl<T:'static, R:'static> M<T, R> for Parser<T, R> where T:Clone, R:Clone {}
+impl<'a, T:'a, R:'a, E:'static> M<'a, T, R, E> for Parser<'a, T, R, E>
+where T:Clone, R:Clone, E:Error+From<ParseError> {}
 
-pub fn parser<T:'static, R:'static>(parsec:Arc<Parsec<T, R>>)->Parser<T, R> where T:Clone, R:Clone {
+pub fn parser<'a, T:'a, R:'a, E:'static>(parsec:Arc<Parsec<T, R, E>+'a>)->Parser<'a, T, R, E>
+where T:Clone, R:Clone {
     Parser::new(parsec)
 }
 
 // A monad just return bind
-pub struct Bind<T, R> {
-    binder: Arc<Box<Fn(&mut State<T>, T)->Status<R>>>,
+pub struct Bind<'a, T, R, E=SimpleError> {
+    binder: Arc<Box<Fn(&mut State<T>, T)->Status<R, E>+'a>>,
 }
 
-impl<T:'static, R:'static> Bind<T, R>
+impl<'a, T:'a, R:'a, E:'static> Bind<'a, T, R, E>
 where T:Clone, R:Clone {
-    pub fn new(binder: Arc<Box<Fn(&mut State<T>, T)->Status<R>>>)-> Bind<T, R> {
+    pub fn new(binder: Arc<Box<Fn(&mut State<T>, T)->Status<R, E>+'a>>)-> Bind<'a, T, R, E> {
         Bind{binder:binder.clone()}
     }
 }
 
-impl<T, R> Parsec<T, R> for Bind<T, R> where T:Clone, R:Clone {
-    fn parse(&self, state: &mut State<T>) -> Status<R> {
-        let n = state.next();
-        n.map_or(Err(SimpleError::new(state.pos(), String::from("eof"))),
-                |x:T| (self.binder)(state, x))
+impl<'a, T, R, E> Parsec<T, R, E> for Bind<'a, T, R, E>
+where T:Clone, R:Clone, E:Error+From<ParseError> {
+    fn parse(&self, state: &mut State<T>) -> Status<R, E> {
+        self.reply(state).status()
+    }
+    fn reply(&self, state: &mut State<T>) -> Reply<R, E> {
+        match state.next() {
+            Some(x) => Reply::Consumed((self.binder)(state, x)),
+            None => {
+                let err = state.eof_error();
+                Reply::Empty(Err(E::from(state.failure(err))))
+            },
+        }
     }
 }
 
-impl<'a, T, R> FnOnce<(&'a mut State<T>, )> for Bind<T, R> where T:Clone, R:Clone {
-    type Output = Status<R>;
-    extern "rust-call" fn call_once(self, _: (&'a mut State<T>, )) -> Status<R> {
+impl<'a, 'b, T, R, E> FnOnce<(&'b mut State<T>, )> for Bind<'a, T, R, E>
+where T:Clone, R:Clone, E:Error+From<ParseError> {
+    type Output = Status<R, E>;
+    extern "rust-call" fn call_once(self, _: (&'b mut State<T>, )) -> Status<R, E> {
         panic!("Not implement!");
     }
 }
 
-impl<'a, T, R> FnMut<(&'a mut State<T>, )> for Bind<T, R> where T:Clone, R:Clone {
-    extern "rust-call" fn call_mut(&mut self, _: (&'a mut State<T>, )) -> Status<R> {
+impl<'a, 'b, T, R, E> FnMut<(&'b mut State<T>, )> for Bind<'a, T, R, E>
+where T:Clone, R:Clone, E:Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'b mut State<T>, )) -> Status<R, E> {
         panic!("Not implement!");
     }
 }
 
-impl<'a, T, R> Fn<(&'a mut State<T>, )> for Bind<T, R> where T:Clone, R:Clone {
-    extern "rust-call" fn call(&self, args: (&'a mut State<T>, )) -> Status<R> {
+impl<'a, 'b, T, R, E> Fn<(&'b mut State<T>, )> for Bind<'a, T, R, E>
+where T:Clone, R:Clone, E:Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'b mut State<T>, )) -> Status<R, E> {
         let (state, ) = args;
         self.parse(state)
     }
 }
 
-impl<T, R> Clone for Bind<T, R> where T:Clone, R:Clone {
+impl<'a, T, R, E> Clone for Bind<'a, T, R, E> where T:Clone, R:Clone {
     fn clone(&self)->Self {
         Bind{binder:self.binder.clone()}
     }
@@ -310,19 +968,39 @@ impl<T, R> Clone for Bind<T, R> where T:Clone, R:Clone {
     }
 }
 
-impl<T, R> Debug for Bind<T, R> where T:Clone, R:Clone{
+impl<'a, T, R, E> Debug for Bind<'a, T, R, E> where T:Clone, R:Clone{
     fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
         "<bind function monad environment>".fmt(formatter)
     }
 }
 
-impl<T:'static, R:'static> M<T, R> for Bind<T, R> where T:Clone, R:Clone {}
+impl<'a, T:'a, R:'a, E:'static> M<'a, T, R, E> for Bind<'a, T, R, E>
+where T:Clone, R:Clone, E:Error+From<ParseError> {}
 
-pub fn bind<T:'static, R:'static>(binder: Arc<Box<Fn(&mut State<T>, T)->Status<R>>>)->Bind<T, R>
+pub fn bind<'a, T:'a, R:'a, E:'static>(binder: Arc<Box<Fn(&mut State<T>, T)->Status<R, E>+'a>>)->Bind<'a, T, R, E>
 where T:Clone, R:Clone {
     Bind::new(binder)
 }
 
+// Run parsec as the top level parser. If it fails, the farthest failure met on the way is
+// reported instead when it is farther than the error, since backtracking often hides the
+// place where input really went wrong. A fatal error from cut is always reported as is.
+pub fn run<'a, T, R, E>(parsec:Arc<Parsec<T, R, E>+'a>, state:&mut State<T>)->Status<R, E>
+where E:Error+From<ParseError> {
+    state.aux_mut().farthest = None;
+    match parsec.parse(state) {
+        Err(err) => match state.farthest_failure() {
+            Some(farthest) => if farthest.pos.index() > err.pos() && !err.is_fatal() {
+                Err(E::from(farthest))
+            } else {
+                Err(err)
+            },
+            None => Err(err),
+        },
+        ok => ok,
+    }
+}
+
 #[macro_export]
 macro_rules! bnd {
     ($x:expr) => (Arc::new(Box::new($x)));
@@ -335,4 +1013,10 @@ macro_rules! arc {
 
 pub mod atom;
 pub mod combinator;
+pub mod diagnostic;
+pub mod expr;
+pub mod partial;
+pub mod recover;
+pub mod report;
+pub mod state;
 pub mod text;
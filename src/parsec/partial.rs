@@ -0,0 +1,234 @@
+use parsec::{State, Error, ParseError, SimpleError, Status, Parsec, Reply, Checkpoint, Aux};
+use std::sync::Arc;
+use std::mem;
+use std::fmt::{Debug, Formatter};
+use std::fmt;
+
+// Chunk state holds the input arrived so far. Until it is closed, running out of buffer means
+// "need more input" rather than eof, and parse_partial answers Partial::Incomplete.
+pub struct ChunkState<T> {
+    // position of buffer[0], tokens before it are committed and dropped
+    base: usize,
+    index: usize,
+    buffer: Vec<T>,
+    closed: bool,
+    incomplete: bool,
+    aux: Aux,
+}
+
+impl<T> ChunkState<T> where T:Clone {
+    pub fn new()->ChunkState<T> {
+        ChunkState{base:0, index:0, buffer:Vec::new(), closed:false, incomplete:false, aux:Aux::default()}
+    }
+
+    pub fn feed(&mut self, chunk:&[T]) {
+        self.buffer.push_all(chunk);
+    }
+
+    // No more input will come, the end of buffer is eof from now on.
+    pub fn close(&mut self) {
+        self.closed = true;
+    }
+
+    pub fn is_closed(&self)->bool {
+        self.closed
+    }
+
+    // True if a parser read past the buffer since last parse_partial started.
+    pub fn is_incomplete(&self)->bool {
+        self.incomplete
+    }
+
+    // Drop tokens before current position, call it between messages to keep buffer small.
+    pub fn commit(&mut self) {
+        let count = self.index - self.base;
+        self.buffer.drain(..count);
+        self.base = self.index;
+    }
+
+    // Tokens buffered but not consumed yet.
+    pub fn rest(&self)->&[T] {
+        &self.buffer[self.index - self.base..]
+    }
+
+    fn at_end(&mut self)->bool {
+        if self.index - self.base < self.buffer.len() {
+            false
+        } else {
+            if !self.closed {
+                self.incomplete = true;
+            }
+            true
+        }
+    }
+}
+
+impl<T> State<T> for ChunkState<T> where T:Clone {
+    fn pos(&self) -> usize {
+        self.index
+    }
+    fn seek_to(&mut self, to:usize) -> bool {
+        if self.base <= to && to <= self.base + self.buffer.len() {
+            self.index = to;
+            true
+        } else {
+            false
+        }
+    }
+    fn next(&mut self)->Option<T>{
+        if self.at_end() {
+            None
+        } else {
+            let item = self.buffer[self.index - self.base].clone();
+            self.index += 1;
+            Some(item)
+        }
+    }
+    fn next_by(&mut self, pred:&Fn(&T)->bool)->Status<T, ParseError>{
+        if self.at_end() {
+            Err(self.eof_error())
+        } else {
+            let item = self.buffer[self.index - self.base].clone();
+            if pred(&item) {
+                self.index += 1;
+                Ok(item)
+            } else {
+                Err(ParseError::at(self.source_pos(), String::from("predicate failed")))
+            }
+        }
+    }
+    fn peek(&mut self)->Option<T>{
+        if self.at_end() {
+            None
+        } else {
+            Some(self.buffer[self.index - self.base].clone())
+        }
+    }
+    fn input_error(&self)->Option<ParseError> {
+        if !self.closed && self.index - self.base >= self.buffer.len() {
+            Some(ParseError::at(self.source_pos(), String::from("need more input")))
+        } else {
+            None
+        }
+    }
+    fn aux(&self)->&Aux {
+        &self.aux
+    }
+    fn aux_mut(&mut self)->&mut Aux {
+        &mut self.aux
+    }
+}
+
+// Result of parse_partial. Done carries the state back, so the rest input could be parsed
+// as next message.
+pub enum Partial<'a, T, R, E=SimpleError> {
+    Done(Status<R, E>, ChunkState<T>),
+    Incomplete(Resume<'a, T, R, E>),
+}
+
+impl<'a, T, R, E> Debug for Partial<'a, T, R, E> where R:Debug, E:Debug {
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        match *self {
+            Partial::Done(ref re, _) => write!(formatter, "<partial done: {:?}>", re),
+            Partial::Incomplete(_) => write!(formatter, "<partial incomplete>"),
+        }
+    }
+}
+
+// Continuation of a parse waiting for more input. Parsers are closures, they can't be paused,
+// so resume runs the parser again over the longer buffer. parse_partial runs it from the start
+// of the message, which stays buffered until it is done. parse_partial_many only runs the item
+// in progress again, items done are kept and their input committed.
+pub struct Resume<'a, T, R, E=SimpleError> {
+    step: Box<FnMut(&mut ChunkState<T>)->Option<Status<R, E>>+'a>,
+    state: ChunkState<T>,
+}
+
+impl<'a, T, R, E> Resume<'a, T, R, E> where T:Clone {
+    pub fn feed(mut self, chunk:&[T])->Partial<'a, T, R, E> {
+        self.state.feed(chunk);
+        self.resume()
+    }
+
+    // Input is over, run the parser with end of buffer as eof.
+    pub fn finish(mut self)->(Status<R, E>, ChunkState<T>) {
+        self.state.close();
+        match self.resume() {
+            Partial::Done(re, state) => (re, state),
+            Partial::Incomplete(_) => unreachable!(),
+        }
+    }
+
+    pub fn state(&self)->&ChunkState<T> {
+        &self.state
+    }
+
+    fn resume(mut self)->Partial<'a, T, R, E> {
+        match (self.step)(&mut self.state) {
+            Some(re) => Partial::Done(re, self.state),
+            None => Partial::Incomplete(self),
+        }
+    }
+}
+
+// One run of parsec from start, None if it read past the buffer. A run knows nothing about the
+// runs before it, so it starts without farthest failure or dropped errors, and the state goes
+// back to start for the next one.
+fn attempt<T, R, E>(parsec:&Parsec<T, R, E>, state:&mut ChunkState<T>, start:&Checkpoint)->Option<Reply<R, E>>
+where T:Clone, E:Error+From<ParseError> {
+    state.incomplete = false;
+    state.aux.farthest = None;
+    state.aux.dropped.clear();
+    let re = parsec.reply(state);
+    if state.incomplete {
+        match state.rollback(start.clone()) {
+            Ok(_) => None,
+            Err(err) => Some(Reply::new(re.is_consumed(), Err(E::from(err)))),
+        }
+    } else {
+        Some(re)
+    }
+}
+
+// Run parsec over the chunks buffered in state. Any parse which read past the buffer before
+// the state is closed is Incomplete, even it succeed, since more input may change the result.
+pub fn parse_partial<'a, T, R, E>(parsec:Arc<Parsec<T, R, E>+'a>, state:ChunkState<T>)->Partial<'a, T, R, E>
+where T:'a+Clone, R:'a, E:'a+Error+From<ParseError> {
+    let start = state.checkpoint();
+    let step = move |state:&mut ChunkState<T>| attempt(&*parsec, state, &start).map(|re| re.status());
+    Resume{step:Box::new(step), state:state}.resume()
+}
+
+// Run parsec over the chunks as many does, until an item fails without consuming input. Each
+// item done is committed, so only the item in progress is buffered and parsed again when more
+// input comes.
+pub fn parse_partial_many<'a, T, R, E>(parsec:Arc<Parsec<T, R, E>+'a>, state:ChunkState<T>)
+    ->Partial<'a, T, Vec<R>, E>
+where T:'a+Clone, R:'a, E:'a+Error+From<ParseError> {
+    let mut items = Vec::new();
+    let step = move |state:&mut ChunkState<T>| loop {
+        let start = state.checkpoint();
+        match attempt(&*parsec, state, &start) {
+            None => return None,
+            Some(Reply::Consumed(Ok(item))) => {
+                items.push(item);
+                state.commit();
+            },
+            Some(Reply::Empty(Ok(_))) => {
+                let message = String::from("many is applied to a parser that accepts empty input");
+                return Some(Err(E::from(state.failure(ParseError::at(state.source_pos(), message)))));
+            },
+            Some(Reply::Consumed(Err(err))) => return Some(Err(err)),
+            Some(Reply::Empty(Err(err))) => {
+                if err.is_fatal() {
+                    return Some(Err(err));
+                }
+                return Some(match state.rollback(start) {
+                    Ok(_) => Ok(mem::replace(&mut items, Vec::new())),
+                    Err(err) => Err(E::from(err)),
+                });
+            },
+        }
+    };
+    Resume{step:Box::new(step), state:state}.resume()
+}
@@ -0,0 +1,207 @@
+use parsec::{State, Error, ParseError, SimpleError, Parsec, Status, M};
+use std::sync::Arc;
+use std::fmt::{Debug, Formatter};
+use std::fmt;
+
+// Recover runs p, if p fails it records the error in the state, rolls back and runs recovery
+// to skip the broken input, then returns None so the enclosing parser keeps going. If the
+// recovery fails or skips nothing, p's error is returned. Errors recorded in a branch later
+// rolled back are dropped with it. Once the state has collected as many errors as its limit,
+// the next error of p is returned as is and parsing stops. A fatal error of p, from a cut, is
+// never recovered over.
+pub struct Recover<'a, T, R, S, E=SimpleError> {
+    parsec: Arc<Parsec<T, R, E>+'a>,
+    recovery: Arc<Parsec<T, S, E>+'a>,
+}
+
+impl<'a, T, R, S, E> Recover<'a, T, R, S, E> {
+    pub fn new(p:Arc<Parsec<T, R, E>+'a>, recovery:Arc<Parsec<T, S, E>+'a>) -> Recover<'a, T, R, S, E> {
+        Recover{parsec:p.clone(), recovery:recovery.clone()}
+    }
+}
+
+impl<'a, T, R, S, E> Parsec<T, Option<R>, E> for Recover<'a, T, R, S, E>
+where E:'static+Error+From<ParseError> {
+    fn parse(&self, state:&mut State<T>)->Status<Option<R>, E> {
+        let checkpoint = state.checkpoint();
+        match self.parsec.parse(state) {
+            Ok(x) => Ok(Some(x)),
+            Err(err) => {
+                let count = state.aux().errors.len();
+                if err.is_fatal() || state.aux().error_limit.map_or(false, |limit| count >= limit) {
+                    return Err(err);
+                }
+                let pos = checkpoint.pos;
+                try!(state.rollback(checkpoint));
+                match self.recovery.parse(state) {
+                    // a recovery skips nothing would make many loop forever at the same point
+                    Ok(_) if state.pos() > pos => {
+                        state.aux_mut().errors.push(Arc::new(err));
+                        Ok(None)
+                    },
+                    _ => Err(err),
+                }
+            }
+        }
+    }
+}
+
+impl<'a, 'b, T, R, S, E> FnOnce<(&'b mut State<T>, )> for Recover<'a, T, R, S, E>
+where E:'static+Error+From<ParseError> {
+    type Output = Status<Option<R>, E>;
+    extern "rust-call" fn call_once(self, _: (&'b mut State<T>, )) -> Status<Option<R>, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, R, S, E> FnMut<(&'b mut State<T>, )> for Recover<'a, T, R, S, E>
+where E:'static+Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'b mut State<T>, )) -> Status<Option<R>, E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, R, S, E> Fn<(&'b mut State<T>, )> for Recover<'a, T, R, S, E>
+where E:'static+Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'b mut State<T>, )) -> Status<Option<R>, E> {
+        let (state, ) = args;
+        self.parse(state)
+    }
+}
+
+impl<'a, T, R, S, E> Clone for Recover<'a, T, R, S, E> {
+    fn clone(&self)->Self {
+        Recover{parsec:self.parsec.clone(), recovery:self.recovery.clone()}
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.parsec = source.parsec.clone();
+        self.recovery = source.recovery.clone();
+    }
+}
+
+impl<'a, T, R, S, E> Debug for Recover<'a, T, R, S, E> {
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        write!(formatter, "<recover parsec>")
+    }
+}
+
+impl<'a, T:'a+Clone, R:'a+Clone, S:'a, E:'static+Error+From<ParseError>> M<'a, T, Option<R>, E>
+for Recover<'a, T, R, S, E>{}
+
+pub fn recover_with<'a, T, R, S, E>(p:Arc<Parsec<T, R, E>+'a>, recovery:Arc<Parsec<T, S, E>+'a>)
+    ->Recover<'a, T, R, S, E> {
+    Recover::new(p, recovery)
+}
+
+// SkipUntil drops tokens until sync matches, sync is consumed too. It stops quietly at the end
+// of input, so the last broken item doesn't need a terminator.
+pub struct SkipUntil<'a, T, S, E=SimpleError> {
+    sync: Arc<Parsec<T, S, E>+'a>,
+}
+
+impl<'a, T, S, E> SkipUntil<'a, T, S, E> {
+    pub fn new(sync:Arc<Parsec<T, S, E>+'a>) -> SkipUntil<'a, T, S, E> {
+        SkipUntil{sync:sync.clone()}
+    }
+}
+
+impl<'a, T, S, E> Parsec<T, (), E> for SkipUntil<'a, T, S, E> where E:Error+From<ParseError> {
+    fn parse(&self, state:&mut State<T>)->Status<(), E> {
+        loop {
+            let checkpoint = state.checkpoint();
+            if self.sync.parse(state).is_ok() {
+                return Ok(());
+            }
+            try!(state.rollback(checkpoint));
+            if state.next().is_none() {
+                return match state.input_error() {
+                    Some(err) => Err(E::from(err)),
+                    None => Ok(()),
+                };
+            }
+        }
+    }
+}
+
+impl<'a, 'b, T, S, E> FnOnce<(&'b mut State<T>, )> for SkipUntil<'a, T, S, E> where E:Error+From<ParseError> {
+    type Output = Status<(), E>;
+    extern "rust-call" fn call_once(self, _: (&'b mut State<T>, )) -> Status<(), E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, S, E> FnMut<(&'b mut State<T>, )> for SkipUntil<'a, T, S, E> where E:Error+From<ParseError> {
+    extern "rust-call" fn call_mut(&mut self, _: (&'b mut State<T>, )) -> Status<(), E> {
+        panic!("Not implement!");
+    }
+}
+
+impl<'a, 'b, T, S, E> Fn<(&'b mut State<T>, )> for SkipUntil<'a, T, S, E> where E:Error+From<ParseError> {
+    extern "rust-call" fn call(&self, args: (&'b mut State<T>, )) -> Status<(), E> {
+        let (state, ) = args;
+        self.parse(state)
+    }
+}
+
+impl<'a, T, S, E> Clone for SkipUntil<'a, T, S, E> {
+    fn clone(&self)->Self {
+        SkipUntil{sync:self.sync.clone()}
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.sync = source.sync.clone();
+    }
+}
+
+impl<'a, T, S, E> Debug for SkipUntil<'a, T, S, E> {
+    fn fmt(&self, formatter:&mut Formatter)->Result<(), fmt::Error> {
+        write!(formatter, "<skip until parsec>")
+    }
+}
+
+impl<'a, T:'a+Clone, S:'a, E:'static+Error+From<ParseError>> M<'a, T, (), E> for SkipUntil<'a, T, S, E>{}
+
+pub fn skip_until<'a, T, S, E>(sync:Arc<Parsec<T, S, E>+'a>)->SkipUntil<'a, T, S, E> {
+    SkipUntil::new(sync)
+}
+
+// Limit how many errors recover_with collects in state, None for no limit. The error met past
+// the limit is not collected but fails the parse, so it comes on top of the limit.
+pub fn set_error_limit<T>(state:&mut State<T>, limit:Option<usize>) {
+    state.aux_mut().error_limit = limit;
+}
+
+// Errors recover_with collected in state so far. The state keeps them untyped, each one as
+// the error type of the recover_with which met it, so errors of other types than E are left
+// out: asking for ParseError loses the errors of a recover_with over a custom error type.
+pub fn collected_errors<T, E>(state:&State<T>)->Vec<E> where E:'static+Clone {
+    state.aux().errors.iter().filter_map(|err| err.downcast_ref::<E>().cloned()).collect()
+}
+
+// Run parsec with recovery, and return the value it reached with all errors met on the way.
+// The value is None if parsec failed at last, and its error is the last of the errors, one more
+// than limit if the limit stopped it. Errors recovered inside parsec with another error type
+// than E are not returned, see collected_errors.
+pub fn parse_recover<'a, T, R, E>(parsec:Arc<Parsec<T, R, E>+'a>, state:&mut State<T>, limit:Option<usize>)
+    ->(Option<R>, Vec<E>) where E:'static+Clone {
+    let (errors, error_limit) = {
+        let aux = state.aux_mut();
+        (aux.errors.split_off(0), aux.error_limit)
+    };
+    state.aux_mut().error_limit = limit;
+    let re = parsec.parse(state);
+    let mut collected = collected_errors(state);
+    {
+        let aux = state.aux_mut();
+        aux.errors = errors;
+        aux.error_limit = error_limit;
+    }
+    match re {
+        Ok(x) => (Some(x), collected),
+        Err(err) => {
+            collected.push(err);
+            (None, collected)
+        }
+    }
+}
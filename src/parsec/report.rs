@@ -0,0 +1,102 @@
+use parsec::Error;
+use parsec::diagnostic::Position;
+use std::iter::repeat;
+
+const RED:&'static str = "\x1b[1;31m";
+const BLUE:&'static str = "\x1b[1;34m";
+const RESET:&'static str = "\x1b[0m";
+
+// Report renders an error against the source it came from:
+//
+//     error: unexpected 'x', expected digit
+//      --> input.txt:2:5
+//       |
+//     2 | let x = 1;
+//       |     ^
+//
+// Errors carry byte offsets, so the source must be the same text the parser read.
+pub struct Report<'a> {
+    source: &'a str,
+    file_name: String,
+    colored: bool,
+}
+
+impl<'a> Report<'a> {
+    pub fn new(source:&'a str)->Report<'a> {
+        Report{source:source, file_name:String::from("<input>"), colored:false}
+    }
+
+    pub fn file_name(mut self, name:&str)->Report<'a> {
+        self.file_name = String::from(name);
+        self
+    }
+
+    // Wrap the header and caret in ANSI colors, for terminals.
+    pub fn colored(mut self, colored:bool)->Report<'a> {
+        self.colored = colored;
+        self
+    }
+
+    pub fn render(&self, err:&Error)->String {
+        let span = err.span();
+        let start = self.boundary(span.start().offset_in(self.source));
+        let end = self.boundary(span.end().offset_in(self.source));
+        let line_start = self.source[..start].char_indices().filter(|&(i, c)| self.is_break(i, c))
+            .last().map_or(0, |(i, _)| i + 1);
+        let line_end = self.source[start..].find(|c| c == '\n' || c == '\r')
+            .map_or(self.source.len(), |i| start + i);
+        let text = self.source[line_start..line_end].trim_right_matches('\r');
+        let line = if span.start().has_line() {
+            span.start().line()
+        } else {
+            Position::of(self.source, line_start).line() + 1
+        };
+        let column = if span.start().has_line() {
+            span.start().column()
+        } else {
+            self.source[line_start..start].chars().count() + 1
+        };
+
+        // keep tabs in the padding so the caret lines up however the terminal shows them
+        let padding:String = self.source[line_start..start].chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+        // span ends past this line are cut at the line end, at least one caret anyway
+        let end = if end > line_end { line_end } else if end < start { start } else { end };
+        let width = self.source[start..end].chars().count();
+        let carets:String = repeat('^').take(if width == 0 { 1 } else { width }).collect();
+
+        let gutter:String = repeat(' ').take(line.to_string().len()).collect();
+        let mut re = String::new();
+        re.push_str(&format!("{}: {}\n", self.paint(RED, "error"), err.message()));
+        re.push_str(&format!("{}{} {}:{}:{}\n", gutter, self.paint(BLUE, "-->"),
+                             self.file_name, line, column));
+        re.push_str(&format!("{} {}\n", gutter, self.paint(BLUE, "|")));
+        re.push_str(&format!("{} {} {}\n", self.paint(BLUE, &line.to_string()),
+                             self.paint(BLUE, "|"), text));
+        re.push_str(&format!("{} {} {}{}\n", gutter, self.paint(BLUE, "|"), padding,
+                             self.paint(RED, &carets)));
+        re
+    }
+
+    // Whether c at offset i ends a line, "\r\n" ends it at the '\n', as Position::of and TextState.
+    fn is_break(&self, i:usize, c:char)->bool {
+        c == '\n' || (c == '\r' && !self.source[i + 1..].starts_with('\n'))
+    }
+
+    // Clamp offset into the source and back to a char boundary.
+    fn boundary(&self, offset:usize)->usize {
+        let mut offset = if offset > self.source.len() { self.source.len() } else { offset };
+        while !self.source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        offset
+    }
+
+    fn paint(&self, color:&str, text:&str)->String {
+        if self.colored {
+            format!("{}{}{}", color, text, RESET)
+        } else {
+            String::from(text)
+        }
+    }
+}
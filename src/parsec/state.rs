@@ -0,0 +1,636 @@
+use parsec::{State, ParseError, Status, SourcePos, Span, Aux};
+use std::iter::FromIterator;
+use std::collections::VecDeque;
+use std::io::{self, Read};
+use std::str;
+
+// Tab width of TextState::new and of a TextState collected from chars.
+const DEFAULT_TAB_WIDTH:usize = 8;
+
+// Text state knows lines and columns of every char, so errors read "line 42, column 7".
+pub struct TextState {
+    index : usize,
+    buffer: Vec<char>,
+    // (index, byte offset) of the first char of every line
+    lines: Vec<(usize, usize)>,
+    tab_width: usize,
+    aux: Aux,
+}
+
+impl TextState {
+    pub fn new(source:&str)->TextState {
+        TextState::with_tab_width(source, DEFAULT_TAB_WIDTH)
+    }
+
+    pub fn with_tab_width(source:&str, tab_width:usize)->TextState {
+        TextState::build(source.chars().collect(), tab_width)
+    }
+
+    fn build(buffer:Vec<char>, tab_width:usize)->TextState {
+        assert!(tab_width > 0, "tab width must be positive");
+        let mut lines = vec![(0, 0)];
+        let mut offset = 0;
+        for (idx, c) in buffer.iter().enumerate() {
+            offset += c.len_utf8();
+            // "\r\n" is one line break, a lonely '\r' is a line break too, same as newline().
+            let brk = match *c {
+                '\n' => true,
+                '\r' => buffer.get(idx + 1) != Some(&'\n'),
+                _ => false,
+            };
+            if brk {
+                lines.push((idx + 1, offset));
+            }
+        }
+        TextState{index:0, buffer:buffer, lines:lines, tab_width:tab_width, aux:Aux::default()}
+    }
+
+    pub fn tab_width(&self)->usize {
+        self.tab_width
+    }
+
+    pub fn pos_of(&self, index:usize)->SourcePos {
+        let index = if index > self.buffer.len() { self.buffer.len() } else { index };
+        let line = match self.lines.binary_search_by(|&(start, _)| start.cmp(&index)) {
+            Ok(line) => line,
+            Err(line) => line - 1,
+        };
+        let (start, mut offset) = self.lines[line];
+        let mut column = 1;
+        for c in self.buffer[start..index].iter() {
+            offset += c.len_utf8();
+            if *c == '\t' {
+                column += self.tab_width - (column - 1) % self.tab_width;
+            } else {
+                column += 1;
+            }
+        }
+        SourcePos::new(index, offset, line + 1, column)
+    }
+}
+
+impl FromIterator<char> for TextState {
+    fn from_iter<T>(iterator: T) -> Self where T:IntoIterator<Item=char> {
+        TextState::build(Vec::from_iter(iterator.into_iter()), DEFAULT_TAB_WIDTH)
+    }
+}
+
+impl State<char> for TextState {
+    fn pos(&self) -> usize {
+        self.index
+    }
+    fn source_pos(&self)->SourcePos {
+        self.pos_of(self.index)
+    }
+    fn span(&self)->Span {
+        Span::new(self.pos_of(self.index), self.pos_of(self.index + 1))
+    }
+    fn seek_to(&mut self, to:usize) -> bool {
+        if to <= self.buffer.len() {
+            self.index = to;
+            true
+        } else {
+            false
+        }
+    }
+    fn next(&mut self)->Option<char>{
+        if 0 as usize <= self.index && self.index < self.buffer.len() {
+            let item = self.buffer[self.index];
+            self.index += 1;
+            Some(item)
+        } else {
+            None
+        }
+    }
+    fn next_by(&mut self, pred:&Fn(&char)->bool)->Status<char, ParseError>{
+        if 0 as usize <= self.index && self.index < self.buffer.len() {
+            let item = self.buffer[self.index];
+            if pred(&item) {
+                self.index += 1;
+                Ok(item)
+            } else {
+                Err(ParseError::at(self.source_pos(), String::from("predicate failed")))
+            }
+        } else {
+            Err(self.eof_error())
+        }
+    }
+    fn peek(&mut self)->Option<char>{
+        self.buffer.get(self.index).cloned()
+    }
+    fn aux(&self)->&Aux {
+        &self.aux
+    }
+    fn aux_mut(&mut self)->&mut Aux {
+        &mut self.aux
+    }
+}
+
+// Tokens which can be read one by one from a byte stream.
+pub trait Decode: Sized {
+    fn decode<R:Read>(bytes:&mut io::Bytes<R>)->Option<io::Result<Self>>;
+}
+
+impl Decode for u8 {
+    fn decode<R:Read>(bytes:&mut io::Bytes<R>)->Option<io::Result<u8>> {
+        bytes.next()
+    }
+}
+
+// Length of the utf-8 sequence starts with this byte, 0 if it can't start a sequence.
+pub fn utf8_width(first:u8)->usize {
+    match first {
+        0x00...0x7F => 1,
+        0xC2...0xDF => 2,
+        0xE0...0xEF => 3,
+        0xF0...0xF4 => 4,
+        _ => 0,
+    }
+}
+
+impl Decode for char {
+    fn decode<R:Read>(bytes:&mut io::Bytes<R>)->Option<io::Result<char>> {
+        let first = match bytes.next() {
+            None => return None,
+            Some(Err(err)) => return Some(Err(err)),
+            Some(Ok(byte)) => byte,
+        };
+        let width = utf8_width(first);
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid utf-8 sequence");
+        if width == 0 {
+            return Some(Err(invalid()));
+        }
+        let mut buf = [first, 0, 0, 0];
+        for idx in 1..width {
+            match bytes.next() {
+                Some(Ok(byte)) => buf[idx] = byte,
+                Some(Err(err)) => return Some(Err(err)),
+                None => return Some(Err(invalid())),
+            }
+        }
+        match str::from_utf8(&buf[..width]) {
+            Ok(s) => s.chars().next().map(|c| Ok(c)),
+            Err(_) => Some(Err(invalid())),
+        }
+    }
+}
+
+// Reader state pulls tokens lazily from a io::Read, and only keeps `window` tokens
+// behind current position for backtracking. Wrap the reader in a io::BufReader, it reads
+// one byte a time.
+pub struct ReaderState<R, T> {
+    source: io::Bytes<R>,
+    // index of buffer[0], nothing before it could be seek back
+    base: usize,
+    index: usize,
+    buffer: VecDeque<T>,
+    window: usize,
+    error: Option<io::Error>,
+    aux: Aux,
+}
+
+impl<R, T> ReaderState<R, T> where R:Read, T:Decode+Clone {
+    pub fn new(reader:R, window:usize)->ReaderState<R, T> {
+        ReaderState{
+            source: reader.bytes(),
+            base: 0,
+            index: 0,
+            buffer: VecDeque::new(),
+            window: window,
+            error: None,
+            aux: Aux::default(),
+        }
+    }
+
+    // Committed point, state can't seek to any position before it.
+    pub fn committed(&self)->usize {
+        self.base
+    }
+
+    // Drop all tokens before current position, parser can't backtrack over them anymore.
+    pub fn commit(&mut self) {
+        while self.base < self.index {
+            self.buffer.pop_front();
+            self.base += 1;
+        }
+    }
+
+    // The io error stopped reading, parsers get it as input error.
+    pub fn io_error(&self)->Option<&io::Error> {
+        self.error.as_ref()
+    }
+
+    fn fill(&mut self)->bool {
+        while self.base + self.buffer.len() <= self.index {
+            if self.error.is_some() {
+                return false;
+            }
+            match T::decode(&mut self.source) {
+                None => return false,
+                Some(Ok(item)) => self.buffer.push_back(item),
+                Some(Err(err)) => {
+                    self.error = Some(err);
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    fn forward(&mut self) {
+        self.index += 1;
+        while self.index - self.base > self.window {
+            self.buffer.pop_front();
+            self.base += 1;
+        }
+    }
+}
+
+impl<R> ReaderState<R, u8> where R:Read {
+    pub fn bytes(reader:R, window:usize)->ReaderState<R, u8> {
+        ReaderState::new(reader, window)
+    }
+}
+
+impl<R> ReaderState<R, char> where R:Read {
+    pub fn chars(reader:R, window:usize)->ReaderState<R, char> {
+        ReaderState::new(reader, window)
+    }
+}
+
+impl<R, T> State<T> for ReaderState<R, T> where R:Read, T:Decode+Clone {
+    fn pos(&self) -> usize {
+        self.index
+    }
+    fn seek_to(&mut self, to:usize) -> bool {
+        if self.base <= to && to <= self.base + self.buffer.len() {
+            self.index = to;
+            true
+        } else {
+            false
+        }
+    }
+    fn next(&mut self)->Option<T>{
+        if self.fill() {
+            let item = self.buffer[self.index - self.base].clone();
+            self.forward();
+            Some(item)
+        } else {
+            None
+        }
+    }
+    fn next_by(&mut self, pred:&Fn(&T)->bool)->Status<T, ParseError>{
+        if self.fill() {
+            let item = self.buffer[self.index - self.base].clone();
+            if pred(&item) {
+                self.forward();
+                Ok(item)
+            } else {
+                Err(ParseError::at(self.source_pos(), String::from("predicate failed")))
+            }
+        } else {
+            Err(self.eof_error())
+        }
+    }
+    fn peek(&mut self)->Option<T>{
+        if self.fill() {
+            Some(self.buffer[self.index - self.base].clone())
+        } else {
+            None
+        }
+    }
+    fn input_error(&self)->Option<ParseError> {
+        self.error.as_ref().map(|err| {
+            ParseError::at(self.source_pos(), format!("read input failed at {}: {}", self.index, err))
+        })
+    }
+    fn aux(&self)->&Aux {
+        &self.aux
+    }
+    fn aux_mut(&mut self)->&mut Aux {
+        &mut self.aux
+    }
+}
+
+// Str state borrows the source text, position is the byte offset of current char, so any
+// two positions slice the source without copy.
+pub struct StrState<'a> {
+    source: &'a str,
+    offset: usize,
+    aux: Aux,
+}
+
+impl<'a> StrState<'a> {
+    pub fn new(source:&'a str)->StrState<'a> {
+        StrState{source:source, offset:0, aux:Aux::default()}
+    }
+
+    pub fn source(&self)->&'a str {
+        self.source
+    }
+
+    // Text not consumed yet.
+    pub fn rest(&self)->&'a str {
+        &self.source[self.offset..]
+    }
+
+    // Text between two positions, as the range recognize returns.
+    pub fn slice(&self, from:usize, to:usize)->&'a str {
+        &self.source[from..to]
+    }
+}
+
+impl<'a> State<char> for StrState<'a> {
+    fn pos(&self) -> usize {
+        self.offset
+    }
+    fn source_pos(&self)->SourcePos {
+        SourcePos::new(self.offset, self.offset, 0, 0)
+    }
+    fn span(&self)->Span {
+        let end = self.offset + self.rest().chars().next().map_or(0, |c| c.len_utf8());
+        Span::new(self.source_pos(), SourcePos::new(end, end, 0, 0))
+    }
+    fn seek_to(&mut self, to:usize) -> bool {
+        if to <= self.source.len() && self.source.is_char_boundary(to) {
+            self.offset = to;
+            true
+        } else {
+            false
+        }
+    }
+    fn next(&mut self)->Option<char>{
+        let item = self.rest().chars().next();
+        item.map(|c| {
+            self.offset += c.len_utf8();
+            c
+        })
+    }
+    fn next_by(&mut self, pred:&Fn(&char)->bool)->Status<char, ParseError>{
+        match self.rest().chars().next() {
+            Some(c) => if pred(&c) {
+                self.offset += c.len_utf8();
+                Ok(c)
+            } else {
+                Err(ParseError::at(self.source_pos(), String::from("predicate failed")))
+            },
+            None => Err(self.eof_error()),
+        }
+    }
+    fn peek(&mut self)->Option<char>{
+        self.rest().chars().next()
+    }
+    fn aux(&self)->&Aux {
+        &self.aux
+    }
+    fn aux_mut(&mut self)->&mut Aux {
+        &mut self.aux
+    }
+}
+
+// Slice state borrows the tokens and yields references to them, tokens never be cloned.
+pub struct SliceState<'a, T:'a> {
+    source: &'a [T],
+    index: usize,
+    aux: Aux,
+}
+
+impl<'a, T> SliceState<'a, T> {
+    pub fn new(source:&'a [T])->SliceState<'a, T> {
+        SliceState{source:source, index:0, aux:Aux::default()}
+    }
+
+    pub fn rest(&self)->&'a [T] {
+        &self.source[self.index..]
+    }
+
+    // Tokens between two positions, as the range recognize returns.
+    pub fn slice(&self, from:usize, to:usize)->&'a [T] {
+        &self.source[from..to]
+    }
+}
+
+impl<'a, T> State<&'a T> for SliceState<'a, T> {
+    fn pos(&self) -> usize {
+        self.index
+    }
+    fn seek_to(&mut self, to:usize) -> bool {
+        if to <= self.source.len() {
+            self.index = to;
+            true
+        } else {
+            false
+        }
+    }
+    fn next(&mut self)->Option<&'a T>{
+        let source = self.source;
+        let item = source.get(self.index);
+        if item.is_some() {
+            self.index += 1;
+        }
+        item
+    }
+    fn next_by(&mut self, pred:&Fn(&&'a T)->bool)->Status<&'a T, ParseError>{
+        let source = self.source;
+        match source.get(self.index) {
+            Some(item) => if pred(&item) {
+                self.index += 1;
+                Ok(item)
+            } else {
+                Err(ParseError::at(self.source_pos(), String::from("predicate failed")))
+            },
+            None => Err(self.eof_error()),
+        }
+    }
+    fn peek(&mut self)->Option<&'a T>{
+        self.source.get(self.index)
+    }
+    fn aux(&self)->&Aux {
+        &self.aux
+    }
+    fn aux_mut(&mut self)->&mut Aux {
+        &mut self.aux
+    }
+}
+
+// Utf8 state decodes chars from bytes lazily, position is the byte offset of current char.
+// Bytes which aren't valid utf-8 stop it with an input error points to their offset.
+pub struct Utf8State<'a> {
+    source: &'a [u8],
+    offset: usize,
+    aux: Aux,
+}
+
+impl<'a> Utf8State<'a> {
+    pub fn new(source:&'a [u8])->Utf8State<'a> {
+        Utf8State{source:source, offset:0, aux:Aux::default()}
+    }
+
+    pub fn rest(&self)->&'a [u8] {
+        &self.source[self.offset..]
+    }
+
+    pub fn slice(&self, from:usize, to:usize)->&'a [u8] {
+        &self.source[from..to]
+    }
+
+    // Char at current offset and its width, Err if the bytes there are not valid utf-8.
+    fn decode(&self)->Option<Result<(char, usize), ()>> {
+        if self.offset >= self.source.len() {
+            return None;
+        }
+        let width = utf8_width(self.source[self.offset]);
+        let end = self.offset + width;
+        if width == 0 || end > self.source.len() {
+            return Some(Err(()));
+        }
+        match str::from_utf8(&self.source[self.offset..end]) {
+            Ok(s) => s.chars().next().map(|c| Ok((c, width))),
+            Err(_) => Some(Err(())),
+        }
+    }
+}
+
+impl<'a> State<char> for Utf8State<'a> {
+    fn pos(&self) -> usize {
+        self.offset
+    }
+    fn source_pos(&self)->SourcePos {
+        SourcePos::new(self.offset, self.offset, 0, 0)
+    }
+    fn span(&self)->Span {
+        // a broken sequence spans its first byte
+        let end = self.offset + match self.decode() {
+            Some(Ok((_, width))) => width,
+            Some(Err(())) => 1,
+            None => 0,
+        };
+        Span::new(self.source_pos(), SourcePos::new(end, end, 0, 0))
+    }
+    fn seek_to(&mut self, to:usize) -> bool {
+        // never stop in the middle of a sequence
+        if to == self.source.len() || (to < self.source.len() && self.source[to] & 0xC0 != 0x80) {
+            self.offset = to;
+            true
+        } else {
+            false
+        }
+    }
+    fn next(&mut self)->Option<char>{
+        match self.decode() {
+            Some(Ok((c, width))) => {
+                self.offset += width;
+                Some(c)
+            },
+            _ => None,
+        }
+    }
+    fn next_by(&mut self, pred:&Fn(&char)->bool)->Status<char, ParseError>{
+        match self.decode() {
+            Some(Ok((c, width))) => if pred(&c) {
+                self.offset += width;
+                Ok(c)
+            } else {
+                Err(ParseError::at(self.source_pos(), String::from("predicate failed")))
+            },
+            _ => Err(self.eof_error()),
+        }
+    }
+    fn peek(&mut self)->Option<char>{
+        match self.decode() {
+            Some(Ok((c, _))) => Some(c),
+            _ => None,
+        }
+    }
+    fn input_error(&self)->Option<ParseError> {
+        match self.decode() {
+            Some(Err(())) => {
+                let message = format!("invalid utf-8 sequence at byte {}", self.offset);
+                Some(ParseError::at(self.source_pos(), message))
+            },
+            _ => None,
+        }
+    }
+    fn aux(&self)->&Aux {
+        &self.aux
+    }
+    fn aux_mut(&mut self)->&mut Aux {
+        &mut self.aux
+    }
+}
+
+// Token state runs parsers over lexer output. Every token keeps the span of source text it
+// came from. Positions in errors index tokens as pos does, their offset, line and column are
+// where the token is in that text.
+pub struct TokenState<T> {
+    index: usize,
+    tokens: Vec<(T, Span)>,
+    aux: Aux,
+}
+
+impl<T> TokenState<T> where T:Clone {
+    pub fn new(tokens:Vec<(T, Span)>)->TokenState<T> {
+        TokenState{index:0, tokens:tokens, aux:Aux::default()}
+    }
+}
+
+impl<T> FromIterator<(T, Span)> for TokenState<T> where T:Clone {
+    fn from_iter<I>(iterator: I) -> Self where I:IntoIterator<Item=(T, Span)> {
+        TokenState::new(Vec::from_iter(iterator.into_iter()))
+    }
+}
+
+impl<T> State<T> for TokenState<T> where T:Clone {
+    fn pos(&self) -> usize {
+        self.index
+    }
+    fn source_pos(&self)->SourcePos {
+        self.span().start()
+    }
+    // Span of current token, at eof it is the empty span after the last token.
+    fn span(&self)->Span {
+        match self.tokens.get(self.index) {
+            Some(&(_, span)) => Span::new(span.start().with_index(self.index), span.end().with_index(self.index + 1)),
+            None => {
+                let end = self.tokens.last().map_or(SourcePos::at(0), |&(_, span)| span.end());
+                Span::new(end.with_index(self.index), end.with_index(self.index))
+            }
+        }
+    }
+    fn seek_to(&mut self, to:usize) -> bool {
+        if to <= self.tokens.len() {
+            self.index = to;
+            true
+        } else {
+            false
+        }
+    }
+    fn next(&mut self)->Option<T>{
+        let item = self.tokens.get(self.index).map(|&(ref token, _)| token.clone());
+        if item.is_some() {
+            self.index += 1;
+        }
+        item
+    }
+    fn next_by(&mut self, pred:&Fn(&T)->bool)->Status<T, ParseError>{
+        let item = self.tokens.get(self.index).map(|&(ref token, _)| token.clone());
+        match item {
+            Some(token) => if pred(&token) {
+                self.index += 1;
+                Ok(token)
+            } else {
+                Err(ParseError::at(self.source_pos(), String::from("predicate failed")))
+            },
+            None => Err(self.eof_error()),
+        }
+    }
+    fn peek(&mut self)->Option<T>{
+        self.tokens.get(self.index).map(|&(ref token, _)| token.clone())
+    }
+    fn aux(&self)->&Aux {
+        &self.aux
+    }
+    fn aux_mut(&mut self)->&mut Aux {
+        &mut self.aux
+    }
+}
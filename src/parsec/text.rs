@@ -1,96 +1,108 @@
-use parsec::{State, SimpleError, Status, Parsec, Monad, M, Bind, parser, bind};
-use parsec::combinator::{Either, either, try, many1};
-use parsec::atom::{OneOf, pack, eq, one_of};
+use parsec::{State, Error, ParseError, Status, Parsec, M, parser};
+use parsec::combinator::{Either, Label, either, try, many1};
+use parsec::atom::{OneOf, SatisfyMap, pack_with, eq_with, one_of_with, satisfy_map_with};
 use std::sync::Arc;
 use std::boxed::Box;
 
+// As the atoms, these make parsers failing with SimpleError, the _with twins are generic over the
+// error type.
 pub fn space() -> OneOf<char> {
-    one_of(&vec![' ', '\t'])
-}
-
-pub fn white_space() -> Bind<char, char> {
-    bind(bnd!(|state: &mut State<char>, x:char|->Status<char> {
-        if x.is_whitespace() {
-            Ok(x)
-        } else {
-            let message = format!("Expect space but got {}", x);
-            Err(SimpleError::new(state.pos(), String::from(message)))
-        }
-    }))
-}
-
-pub fn newline() -> Either<char, String> {
-    let rel = eq('\r');
-    let nl = eq('\n');
-    let thn = arc!(either(arc!(try(arc!(nl.clone())).then(arc!(pack(String::from("\r\n"))))),
-                            arc!(pack(String::from("\r")))));
-    either(arc!(rel.then(thn.clone())), arc!(nl.then(arc!(pack(String::from("\n"))))))
-}
-
-pub fn digit() -> Bind<char, char> {
-    bind(bnd!(|state: &mut State<char>, x:char|->Status<char> {
-        if x.is_numeric() {
-            Ok(x)
-        } else {
-            let message = format!("Expect numeric but got {}", x);
-            Err(SimpleError::new(state.pos(), String::from(message)))
-        }
-    }))
-}
-
-pub fn alpha() -> Bind<char, char> {
-    bind(bnd!(|state: &mut State<char>, x:char|->Status<char> {
-        if x.is_alphabetic() {
-            Ok(x)
-        } else {
-            let message = format!("Expect alphabetic but got {}", x);
-            Err(SimpleError::new(state.pos(), String::from(message)))
-        }
-    }))
-}
-
-pub fn alphanumeric() -> Bind<char, char> {
-    bind(bnd!(|state: &mut State<char>, x:char|->Status<char> {
-        if x.is_alphanumeric() {
-            Ok(x)
-        } else {
-            let message = format!("Expect alphabetic or number but got {}", x);
-            Err(SimpleError::new(state.pos(), String::from(message)))
-        }
-    }))
-}
-
-pub fn control() -> Bind<char, char> {
-    bind(bnd!(|state: &mut State<char>, x:char|->Status<char> {
-        if x.is_control() {
-            Ok(x)
-        } else {
-            let message = format!("Expect control but got {}", x);
-            Err(SimpleError::new(state.pos(), String::from(message)))
-        }
-    }))
-}
-
-pub fn uinteger() -> Monad<char, Vec<char>, String> {
-    parser(arc!(many1(arc!(digit())))).bind(bnd!(|_:&mut State<char>, x:Vec<char>| -> Status<String> {
+    space_with()
+}
+
+pub fn space_with<E>() -> OneOf<char, E> {
+    one_of_with(&vec![' ', '\t'])
+}
+
+pub fn white_space() -> SatisfyMap<char, char> {
+    white_space_with()
+}
+
+pub fn white_space_with<E>() -> SatisfyMap<char, char, E> {
+    char_class("white space", char::is_whitespace)
+}
+
+pub fn newline<'a>() -> Either<'a, char, String> {
+    newline_with()
+}
+
+pub fn newline_with<'a, E:'static+Error+From<ParseError>>() -> Either<'a, char, String, E> {
+    let rel = eq_with('\r');
+    let nl = eq_with('\n');
+    let thn = arc!(either(arc!(try(arc!(nl.clone())).then(arc!(pack_with(String::from("\r\n"))))),
+                            arc!(pack_with(String::from("\r")))));
+    either(arc!(rel.then(thn.clone())), arc!(nl.then(arc!(pack_with(String::from("\n"))))))
+}
+
+// Parsec of one char matches pred, a mismatched char is not consumed.
+fn char_class<E>(description:&str, pred:fn(char)->bool) -> SatisfyMap<char, char, E> {
+    satisfy_map_with(String::from(description), arc!(move |x:&char| if pred(*x) { Some(*x) } else { None }))
+}
+
+pub fn digit() -> SatisfyMap<char, char> {
+    digit_with()
+}
+
+pub fn digit_with<E>() -> SatisfyMap<char, char, E> {
+    char_class("digit", char::is_numeric)
+}
+
+pub fn alpha() -> SatisfyMap<char, char> {
+    alpha_with()
+}
+
+pub fn alpha_with<E>() -> SatisfyMap<char, char, E> {
+    char_class("letter", char::is_alphabetic)
+}
+
+pub fn alphanumeric() -> SatisfyMap<char, char> {
+    alphanumeric_with()
+}
+
+pub fn alphanumeric_with<E>() -> SatisfyMap<char, char, E> {
+    char_class("letter or digit", char::is_alphanumeric)
+}
+
+pub fn control() -> SatisfyMap<char, char> {
+    control_with()
+}
+
+pub fn control_with<E>() -> SatisfyMap<char, char, E> {
+    char_class("control char", char::is_control)
+}
+
+pub fn uinteger<'a>() -> Label<'a, char, String> {
+    uinteger_with()
+}
+
+pub fn uinteger_with<'a, E:'static+Error+From<ParseError>>() -> Label<'a, char, String, E> {
+    parser(arc!(many1(arc!(digit_with())))).bind(bnd!(|_:&mut State<char>, x:Vec<char>| -> Status<String, E> {
         Ok(x.iter().cloned().collect::<String>())
-    }))
+    })).label(String::from("unsigned integer"))
+}
+
+pub fn integer<'a>() ->Label<'a, char, String>{
+    integer_with()
 }
 
-pub fn integer() ->Either<char, String>{
-    either(arc!(try(arc!(eq('-'))).bind(bnd!(|state: &mut State<char>, _:char|-> Status<String> {
-        uinteger().parse(state).map(|x:String|->String{
+pub fn integer_with<'a, E:'static+Error+From<ParseError>>() ->Label<'a, char, String, E>{
+    either(arc!(try(arc!(eq_with('-'))).bind(bnd!(|state: &mut State<char>, _:char|-> Status<String, E> {
+        uinteger_with().parse(state).map(|x:String|->String{
             let mut re = String::from("-");
             re.push_str(x.as_str());
             re
         })
-    }))), arc!(uinteger()))
+    }))), arc!(uinteger_with())).label(String::from("integer"))
 }
 
-pub fn ufloat() -> Monad<char, String, String> {
-    let left = either(arc!(uinteger()), arc!(pack(String::from("0"))));
-    let right = uinteger();
-    left.over(arc!(eq('.'))).bind(bnd!(move |state: &mut State<char>, x:String|->Status<String> {
+pub fn ufloat<'a>() -> Label<'a, char, String> {
+    ufloat_with()
+}
+
+pub fn ufloat_with<'a, E:'static+Error+From<ParseError>>() -> Label<'a, char, String, E> {
+    let left = either(arc!(uinteger_with()), arc!(pack_with(String::from("0"))));
+    let right = uinteger_with();
+    left.over(arc!(eq_with('.'))).bind(bnd!(move |state: &mut State<char>, x:String|->Status<String, E> {
         let right = right.clone();
         let rer = right.parse(state);
         rer.map(|r:String|->String{
@@ -99,15 +111,19 @@ pub fn ufloat() -> Monad<char, String, String> {
             re.push_str(r.as_str());
             re
         })
-    }))
+    })).label(String::from("unsigned floating point number"))
+}
+
+pub fn float<'a>() ->Label<'a, char, String>{
+    float_with()
 }
 
-pub fn float() ->Either<char, String>{
-    either(arc!(try(arc!(eq('-'))).bind(bnd!(|state: &mut State<char>, _:char|-> Status<String> {
-        ufloat().parse(state).map(|x:String|->String{
+pub fn float_with<'a, E:'static+Error+From<ParseError>>() ->Label<'a, char, String, E>{
+    either(arc!(try(arc!(eq_with('-'))).bind(bnd!(|state: &mut State<char>, _:char|-> Status<String, E> {
+        ufloat_with().parse(state).map(|x:String|->String{
             let mut re = String::from("-");
             re.push_str(x.as_str());
             re
         })
-    }))), arc!(ufloat()))
+    }))), arc!(ufloat_with())).label(String::from("floating point number"))
 }
@@ -1,9 +1,25 @@
 #![feature(vec_push_all)]
 #[macro_use]
 extern crate ruskell;
-use ruskell::parsec::{VecState, State, Status, Parsec, Error, monad, M, parser};
-use ruskell::parsec::atom::{one, eq, eof, one_of, none_of, ne};
-use ruskell::parsec::combinator::{either, many, many1, between, many_tail, many1_tail};
+use ruskell::parsec::{VecState, State, Status, Parsec, Error, ParseError, SimpleError, SourcePos, Span, Reply, Monad, monad, run, M,
+    parser};
+use ruskell::parsec::atom::{Equal, NotEqual, SatisfyMap, one, eq, eof, one_of, none_of, ne, pack, get_state, put_state, modify_state,
+                              token, satisfy_map, eq_with, eof_with, one_of_with, pack_with, token_with};
+use ruskell::parsec::combinator::{either, many, many1, between, many_tail, many1_tail, try,
+                                  recognize, recognize_str, recognize_slice, label, context,
+                                  cut, sep_by, skip_many, chainl1, chainr1, count, many_m_n, at_least, at_most,
+                                  skip_count, skip_at_most, sep_end_by, sep_end_by1, end_by, end_by1, delimited_list,
+                                  optional, option, look_ahead, not_followed_by};
+use ruskell::parsec::state::{TextState, ReaderState, StrState, SliceState, Utf8State, TokenState};
+use ruskell::parsec::report::Report;
+use ruskell::parsec::diagnostic::{Diagnostic, Position, Severity};
+use ruskell::parsec::diagnostic;
+use ruskell::parsec::expr::{Operator, Assoc, Unary, Binary, expression};
+use ruskell::parsec::combinator::pratt::{Pratt, pratt};
+use ruskell::parsec::recover::{recover_with, skip_until, parse_recover, collected_errors};
+use ruskell::parsec::partial::{ChunkState, Partial, parse_partial, parse_partial_many};
+use ruskell::parsec::text::{newline, alpha, digit, space, uinteger, float, alpha_with, digit_with, uinteger_with,
+                            float_with};
 use std::sync::Arc;
 use std::iter::FromIterator;
 
@@ -417,3 +433,1135 @@ fn many1_tail_test_1() {
     let ver = "This is a string.".chars().into_iter().collect::<Vec<char>>();
     assert_eq!(data, ver);
 }
+
+#[test]
+fn text_state_test_0() {
+    let mut state = TextState::new("ab\ncd\r\nef\rg");
+    let re = many(Arc::new(ne('g')))(&mut state);
+    assert!(re.is_ok());
+    let pos = state.source_pos();
+    assert_eq!(pos.line(), 4);
+    assert_eq!(pos.column(), 1);
+    assert_eq!(pos.offset(), Some(10));
+    assert_eq!(state.pos_of(4).line(), 2);
+    assert_eq!(state.pos_of(4).column(), 2);
+}
+
+#[test]
+fn text_state_test_1() {
+    // collected from chars it has the tab width of new
+    assert_eq!(TextState::from_iter("a".chars()).tab_width(), TextState::new("a").tab_width());
+    let mut state = TextState::with_tab_width("a\tb\nxyz", 4);
+    let re = many(Arc::new(ne('b')))(&mut state);
+    assert!(re.is_ok());
+    assert_eq!(state.source_pos().column(), 5);
+    let re = between(Arc::new(eq('b')), Arc::new(newline()), Arc::new(eq('x'))).then(Arc::new(eq('z')))(&mut state);
+    let err = re.unwrap_err();
+    assert_eq!(err.pos(), 5);
+    assert_eq!(err.source_pos().line(), 2);
+    assert_eq!(err.source_pos().column(), 2);
+    assert_eq!(err.message(), "unexpected 'y', expected 'z'");
+}
+
+#[test]
+fn reader_state_test_0() {
+    let source = "a, b, c".as_bytes();
+    let mut state = ReaderState::chars(source, 4);
+    let re = many(Arc::new(many1_tail(Arc::new(none_of(&vec![',', ' '])), Arc::new(many(Arc::new(one_of(&vec![',', ' '])))))))(&mut state);
+    assert_eq!(re.unwrap(), vec![vec!['a'], vec!['b'], vec!['c']]);
+    assert!(eof()(&mut state).is_ok());
+    assert!(state.io_error().is_none());
+}
+
+#[test]
+fn reader_state_test_1() {
+    let source = "abcdefgh".as_bytes();
+    let mut state:ReaderState<&[u8], u8> = ReaderState::bytes(source, 2);
+    let p = try(Arc::new(many1(Arc::new(ne(b'g'))).then(Arc::new(eq(b'x')))));
+    let re = p(&mut state);
+    assert!(re.unwrap_err().message().contains("behind the committed point"));
+    assert_eq!(state.committed(), 4);
+    assert!(state.seek_to(5));
+    state.commit();
+    assert!(!state.seek_to(4));
+    assert_eq!(state.next(), Some(b'f'));
+}
+
+#[test]
+fn reader_state_test_2() {
+    // a mismatch peeks at the token, so it needs no backtrack window
+    let mut state:ReaderState<&[u8], u8> = ReaderState::bytes("abc".as_bytes(), 0);
+    let err:ParseError = eq_with(b'x').parse(&mut state).unwrap_err();
+    assert_eq!(err.unexpected(), Some("97"));
+    assert_eq!(either(Arc::new(eq(b'x')), Arc::new(eq(b'a'))).parse(&mut state), Ok(b'a'));
+    assert_eq!(state.committed(), 1);
+}
+
+#[test]
+fn str_state_test_0() {
+    let source = "größe = 42";
+    let mut state = StrState::new(source);
+    let ident = recognize(Arc::new(many1(Arc::new(alpha()))));
+    let re = ident(&mut state).unwrap();
+    assert_eq!(state.slice(re.start, re.end), "größe");
+    assert_eq!(state.pos(), 7);
+    assert_eq!(state.rest(), " = 42");
+    let re = try(Arc::new(many(Arc::new(space())).then(Arc::new(eq('+')))))(&mut state);
+    assert!(re.is_err());
+    assert_eq!(state.pos(), 7);
+    let number = recognize(Arc::new(uinteger()));
+    let re = many(Arc::new(none_of(&vec!['4']))).then(Arc::new(number))(&mut state).unwrap();
+    assert_eq!(state.slice(re.start, re.end), "42");
+    assert_eq!(state.slice(0, 2), "gr");
+}
+
+#[test]
+fn str_state_test_1() {
+    // recognized text borrows the source, so a bind chain returns it and it outlives the state
+    let source = String::from("key = value");
+    let entry = {
+        let word = recognize_str(&source, Arc::new(many1(Arc::new(alpha()))));
+        let blank = many(Arc::new(space()));
+        let value = word.clone();
+        let mut state = StrState::new(&source);
+        word.over(Arc::new(blank.clone())).over(Arc::new(eq('='))).over(Arc::new(blank))
+            .bind(Arc::new(Box::new(move |state:&mut State<char>, key| {
+                value.parse(state).map(|value| (key, value))
+            }))).parse(&mut state).unwrap()
+    };
+    assert_eq!(entry, ("key", "value"));
+}
+
+#[test]
+fn slice_state_test_0() {
+    let source = vec![1, 2, 3, 0, 4];
+    let (re, rest) = {
+        let mut state = SliceState::new(&source);
+        let re = recognize_slice(&source, Arc::new(many(Arc::new(ne(&0)))))(&mut state).unwrap();
+        assert_eq!(state.next(), Some(&0));
+        (re, state.rest())
+    };
+    assert_eq!(re, &[1, 2, 3]);
+    assert_eq!(rest, &[4]);
+}
+
+#[test]
+fn user_state_test_0() {
+    let mut state = VecState::from_iter("aab".chars());
+    state.set_user_state(Some(Arc::new(0usize)));
+    let count = Arc::new(modify_state(Arc::new(|x:usize| x + 1)));
+    let a = eq('a').over(count.clone());
+    // first branch counts an 'a' and then fails, Try puts the counter back
+    let ab = try(Arc::new(eq('a').then(count.clone()).then(Arc::new(eq('c')))));
+    let re = many(Arc::new(either(Arc::new(ab), Arc::new(a)))).then(Arc::new(get_state::<char, usize>()))(&mut state);
+    assert_eq!(re.unwrap(), 2);
+    let re = put_state(String::from("done")).then(Arc::new(get_state::<char, String>()))(&mut state);
+    assert_eq!(re.unwrap(), "done");
+    assert!(get_state::<char, usize>()(&mut state).is_err());
+}
+
+#[test]
+fn user_state_test_1() {
+    // a missing or mistyped user state is a failure as any other, even when an alternative hides it
+    let count = Arc::new(either(Arc::new(get_state::<char, usize>()), Arc::new(pack(0usize))));
+    let mut state = VecState::from_iter("a".chars());
+    assert_eq!(run(count.clone(), &mut state), Ok(0));
+    assert_eq!(state.farthest_failure().unwrap().message(), "user state is not set");
+    let mut state = VecState::from_iter("a".chars());
+    state.set_user_state(Some(Arc::new(String::from("one"))));
+    assert_eq!(run(count.clone(), &mut state), Ok(0));
+    assert_eq!(state.farthest_failure().unwrap().message(), "user state type mismatch");
+}
+
+#[test]
+fn checkpoint_test_0() {
+    let mut state = VecState::from_iter("ab".chars());
+    state.set_user_state(Some(Arc::new(1)));
+    assert_eq!(state.next(), Some('a'));
+    let checkpoint = state.checkpoint();
+    assert_eq!(state.next(), Some('b'));
+    assert_eq!(state.next(), None);
+    state.set_user_state(None);
+    let eof_point = state.checkpoint();
+    assert!(state.rollback(checkpoint).is_ok());
+    assert_eq!(state.pos(), 1);
+    assert!(state.user_state().is_some());
+    assert!(state.rollback(eof_point).is_ok());
+    assert_eq!(state.pos(), 2);
+    assert!(state.user_state().is_none());
+}
+
+#[test]
+fn checkpoint_test_1() {
+    let mut state = TextState::new("ab");
+    state.next();
+    state.next();
+    // fails at the end of input after put the user state, Try must undo it
+    let re = try(Arc::new(put_state(1).then(Arc::new(eq('c')))))(&mut state);
+    assert!(re.is_err());
+    assert!(state.user_state().is_none());
+    assert_eq!(state.pos(), 2);
+    let re = many(Arc::new(pack::<char, char>('x')))(&mut state);
+    assert!(re.is_err());
+}
+
+#[test]
+fn utf8_state_test_0() {
+    let source = "héllo wörld".as_bytes();
+    let mut state = Utf8State::new(source);
+    let re = many1(Arc::new(alpha()))(&mut state);
+    assert_eq!(re.unwrap().into_iter().collect::<String>(), "héllo");
+    assert_eq!(state.pos(), 6);
+    let re = eq(' ').then(Arc::new(eq('x')))(&mut state);
+    let err = re.unwrap_err();
+    assert_eq!(err.pos(), 7);
+    assert_eq!(err.source_pos().offset(), Some(7));
+    let re = many1_tail(Arc::new(alpha()), Arc::new(eof()))(&mut state);
+    assert!(re.is_ok());
+}
+
+#[test]
+fn utf8_state_test_1() {
+    let source:&[u8] = &[b'a', b'b', 0xE4, 0xBD, b'c'];
+    let mut state = Utf8State::new(source);
+    let re = many(Arc::new(one()))(&mut state);
+    assert_eq!(re.unwrap(), vec!['a', 'b']);
+    let err = one()(&mut state).unwrap_err();
+    assert_eq!(err.pos(), 2);
+    assert_eq!(err.message(), "invalid utf-8 sequence at byte 2");
+    assert!(eof()(&mut state).is_err());
+    assert!(!state.seek_to(3));
+}
+
+#[test]
+fn partial_test_0() {
+    let frame = Arc::new(many1_tail(Arc::new(ne(';')), Arc::new(eq(';'))));
+    let mut state = ChunkState::new();
+    state.feed(&"ab".chars().collect::<Vec<char>>());
+    let resume = match parse_partial(frame.clone(), state) {
+        Partial::Incomplete(resume) => resume,
+        other => panic!("expect incomplete but got {:?}", other),
+    };
+    let (re, mut state) = match resume.feed(&"c;de".chars().collect::<Vec<char>>()) {
+        Partial::Done(re, state) => (re, state),
+        other => panic!("expect done but got {:?}", other),
+    };
+    assert_eq!(re.unwrap(), vec!['a', 'b', 'c']);
+    state.commit();
+    assert_eq!(state.rest(), &['d', 'e']);
+    let resume = match parse_partial(frame.clone(), state) {
+        Partial::Incomplete(resume) => resume,
+        other => panic!("expect incomplete but got {:?}", other),
+    };
+    let (re, _) = resume.finish();
+    let err = re.unwrap_err();
+    assert_eq!(err.pos(), 6);
+    assert_eq!(err.message(), "unexpected end of input, expected ';'");
+}
+
+#[test]
+fn partial_test_1() {
+    let mut state = ChunkState::new();
+    state.feed(&[1, 2]);
+    let re = eq(1).then(Arc::new(eq(3)))(&mut state);
+    assert!(re.is_err());
+    assert!(!state.is_incomplete());
+    let err = eq(2).then(Arc::new(eof()))(&mut state).unwrap_err();
+    assert!(state.is_incomplete());
+    assert_eq!(err.message(), "need more input");
+}
+
+#[test]
+fn partial_test_2() {
+    // items done are committed, only the one in progress is parsed again
+    let frame = Arc::new(many1_tail(Arc::new(NotEqual::<char, SimpleError>::new(';')), Arc::new(Equal::new(';'))));
+    let mut state = ChunkState::new();
+    state.feed(&"ab;c".chars().collect::<Vec<char>>());
+    let resume = match parse_partial_many(frame.clone(), state) {
+        Partial::Incomplete(resume) => resume,
+        other => panic!("expect incomplete but got {:?}", other),
+    };
+    assert_eq!(resume.state().rest(), &['c']);
+    let resume = match resume.feed(&"d;".chars().collect::<Vec<char>>()) {
+        Partial::Incomplete(resume) => resume,
+        other => panic!("expect incomplete but got {:?}", other),
+    };
+    assert!(resume.state().rest().is_empty());
+    let (re, _) = resume.finish();
+    assert_eq!(re.unwrap(), vec![vec!['a', 'b'], vec!['c', 'd']]);
+
+    // a run doesn't see the failures of the runs before it
+    let p = Arc::new(either(Arc::new(try(Arc::new(eq('a').then(Arc::new(eq('b')))))), Arc::new(eq('a'))));
+    let mut state = ChunkState::new();
+    state.feed(&['a']);
+    let resume = match parse_partial(p, state) {
+        Partial::Incomplete(resume) => resume,
+        other => panic!("expect incomplete but got {:?}", other),
+    };
+    let (re, state) = match resume.feed(&['x']) {
+        Partial::Done(re, state) => (re, state),
+        other => panic!("expect done but got {:?}", other),
+    };
+    assert_eq!(re, Ok('a'));
+    assert_eq!(state.farthest_failure().unwrap().message(), "unexpected 'x', expected 'b'");
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Ident(String),
+    Num(i64),
+    Plus,
+}
+
+fn lex(source:&str)->TokenState<Tok> {
+    let mut state = TextState::new(source);
+    let mut tokens = Vec::new();
+    loop {
+        many(Arc::new(space()))(&mut state).unwrap();
+        let start = state.source_pos();
+        let tok = match state.next() {
+            None => break,
+            Some('+') => Tok::Plus,
+            Some(c) if c.is_numeric() => Tok::Num(c.to_digit(10).unwrap() as i64),
+            Some(c) => Tok::Ident(c.to_string()),
+        };
+        tokens.push((tok, Span::new(start, state.source_pos())));
+    }
+    TokenState::from_iter(tokens)
+}
+
+#[test]
+fn token_state_test_0() {
+    let mut state = lex("1 + x");
+    let num = satisfy_map(String::from("number"), Arc::new(|t:&Tok| match *t {
+        Tok::Num(n) => Some(n),
+        _ => None,
+    }));
+    let re = num.clone().over(Arc::new(token(Tok::Plus)))(&mut state);
+    assert_eq!(re.unwrap(), 1);
+    assert_eq!(state.span().start().column(), 5);
+    let err = num(&mut state).unwrap_err();
+    assert_eq!(err.source_pos().column(), 5);
+    assert_eq!(err.message(), "unexpected Ident(\"x\"), expected number");
+    assert_eq!(state.pos(), 2);
+    assert_eq!(token(Tok::Ident(String::from("x")))(&mut state).unwrap(), Tok::Ident(String::from("x")));
+    assert_eq!(token(Tok::Plus)(&mut state).unwrap_err().source_pos().column(), 6);
+}
+
+#[test]
+fn token_state_test_1() {
+    let mut state = lex("1  +  x");
+    let err = token(Tok::Plus)(&mut state).unwrap_err();
+    assert_eq!(err.pos(), 0);
+    token(Tok::Num(1)).then(Arc::new(token(Tok::Plus)))(&mut state).unwrap();
+    let err = token(Tok::Plus)(&mut state).unwrap_err();
+    assert_eq!(err.pos(), state.pos());
+    assert_eq!(err.pos(), 2);
+    assert_eq!(err.source_pos().column(), 7);
+    assert_eq!(state.span().end().index(), 3);
+    token(Tok::Ident(String::from("x")))(&mut state).unwrap();
+    let err = token(Tok::Plus)(&mut state).unwrap_err();
+    assert_eq!(err.pos(), 3);
+    assert_eq!(err.source_pos().column(), 8);
+}
+
+#[test]
+fn parse_error_test_0() {
+    let mut state = TextState::new("(x");
+    let term = either(Arc::new(digit_with()), Arc::new(eq_with('-'))).or(Arc::new(eq_with('(')));
+    let re = eq_with('(').then(Arc::new(term))(&mut state);
+    let err:ParseError = re.unwrap_err();
+    assert_eq!(err.source_pos().column(), 2);
+    assert_eq!(err.unexpected(), Some("'x'"));
+    assert_eq!(err.expected(), &[String::from("digit"), String::from("'-'"), String::from("'('")]);
+    assert_eq!(err.message(), "unexpected 'x', expected digit, '-' or '('");
+    assert_eq!(format!("{}", err), "line 1, column 2: unexpected 'x', expected digit, '-' or '('");
+}
+
+#[test]
+fn parse_error_test_1() {
+    let pos = SourcePos::at(3);
+    let left = ParseError::unexpected_at(pos, String::from("'x'")).expect(String::from("digit"));
+    let right = ParseError::at(pos, String::from("bad")).expect(String::from("digit")).expect(String::from("'.'"));
+    let err = left.clone().merge(right);
+    assert_eq!(err.message(), "unexpected 'x', expected digit or '.', bad");
+    let farther = ParseError::at(SourcePos::at(4), String::from("farther"));
+    assert_eq!(left.merge(farther.clone()), farther);
+}
+
+#[test]
+fn label_test_0() {
+    let mut state = TextState::new("x1");
+    let err:ParseError = float_with()(&mut state).unwrap_err();
+    assert_eq!(err.message(), "unexpected 'x', expected floating point number");
+    let p = label(Arc::new(either(Arc::new(eq_with('a')), Arc::new(eq_with('b')))), String::from("a or b"));
+    let err:ParseError = p(&mut state).unwrap_err();
+    assert_eq!(err.expected(), &[String::from("a or b")]);
+    // errors after consuming input keep the inner expectation
+    let mut state = TextState::new("1.x");
+    let err:ParseError = float_with()(&mut state).unwrap_err();
+    assert_eq!(err.message(), "unexpected 'x', expected unsigned integer");
+    let err:ParseError = eq_with('1').then(Arc::new(eq_with('+'))).label(String::new())(&mut TextState::new("x")).unwrap_err();
+    assert_eq!(err.message(), "unexpected 'x'");
+}
+
+#[test]
+fn label_test_1() {
+    // the default error tells nothing but its message, the label replaces all of it
+    let err = float()(&mut TextState::new("x1")).unwrap_err();
+    assert_eq!(err.message(), "expected floating point number");
+    let p = label(Arc::new(either(Arc::new(eq('a')), Arc::new(eq('b')))), String::from("a or b"));
+    assert_eq!(format!("{}", p(&mut TextState::new("x")).unwrap_err()), "line 1, column 1: expected a or b");
+    let err = float()(&mut TextState::new("1.x")).unwrap_err();
+    assert_eq!(err.message(), "expected unsigned integer");
+}
+
+#[test]
+fn label_test_2() {
+    // what p dropped at the label position is expected as the label too
+    let sign = label(Arc::new(eq_with::<_, ParseError>('-').optional().then(Arc::new(eq_with('+').optional()))), String::from("sign"));
+    let p = parser(Arc::new(sign.clone())).then(Arc::new(digit_with()));
+    let err = p.parse(&mut TextState::new("x")).unwrap_err();
+    assert_eq!(err.message(), "unexpected 'x', expected sign or digit");
+    let err = p.parse(&mut TextState::new("-x")).unwrap_err();
+    assert_eq!(err.message(), "unexpected 'x', expected '+' or digit");
+    let q = label(Arc::new(eq_with::<_, ParseError>('-').optional().then(Arc::new(eq_with('+')))), String::from("sign"));
+    let err = q.parse(&mut TextState::new("x")).unwrap_err();
+    assert_eq!(err.message(), "unexpected 'x', expected sign");
+}
+
+#[test]
+fn report_test_0() {
+    let source = "let x = 1;\nlet y = ?;";
+    let mut state = TextState::new(source);
+    let p = many(Arc::new(ne('?'))).then(Arc::new(digit()));
+    let err = p(&mut state).unwrap_err();
+    let report = Report::new(source).file_name("input.txt").render(&err);
+    assert_eq!(report, "error: unexpected '?', expected digit\n \
+                         --> input.txt:2:9\n  \
+                         |\n\
+                        2 | let y = ?;\n  \
+                          |         ^\n");
+    let colored = Report::new(source).colored(true).render(&err);
+    assert!(colored.contains("\x1b[1;31m^\x1b[0m"));
+}
+
+#[test]
+fn report_test_1() {
+    // flat states have no line information, the report counts it from the source
+    let source = "ab\n\tcd";
+    let mut state = StrState::new(source);
+    let err = many(Arc::new(ne('d'))).then(Arc::new(eof()))(&mut state).unwrap_err();
+    let report = Report::new(source).render(&err);
+    assert!(report.contains(" --> <input>:2:3\n"));
+    assert!(report.ends_with("2 | \tcd\n  | \t ^\n"));
+    // a VecState knows token indexes only, the report takes them as chars of the source
+    let source = "é?";
+    let mut state = VecState::from_iter(source.chars());
+    let err = eq('é').then(Arc::new(digit()))(&mut state).unwrap_err();
+    assert_eq!(err.source_pos().offset(), None);
+    assert_eq!(err.source_pos().offset_in(source), 2);
+    assert!(Report::new(source).render(&err).contains(" --> <input>:1:2\n"));
+}
+
+#[test]
+fn report_test_2() {
+    // a lone '\r' breaks lines as "\n" and "\r\n" do, as TextState counts them
+    let source = "ab\rcd\r\nx?";
+    let p = many(Arc::new(ne('d'))).then(Arc::new(eof()));
+    let err = p.clone()(&mut TextState::new(source)).unwrap_err();
+    assert!(Report::new(source).render(&err).ends_with("2 | cd\n  |  ^\n"));
+    let err = p(&mut StrState::new(source)).unwrap_err();
+    let report = Report::new(source).render(&err);
+    assert!(report.contains(" --> <input>:2:2\n"));
+    assert!(report.ends_with("2 | cd\n  |  ^\n"));
+    let err = many(Arc::new(ne('?'))).then(Arc::new(digit()))(&mut StrState::new(source)).unwrap_err();
+    assert!(Report::new(source).render(&err).ends_with("3 | x?\n  |  ^\n"));
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Code {
+    Syntax,
+    Overflow,
+    Reserved,
+}
+
+// error of a domain parser, carries a typed code beside the message
+#[derive(Debug, Clone)]
+struct DomainError {
+    pos: SourcePos,
+    code: Code,
+    message: String,
+}
+
+impl Error for DomainError {
+    fn pos(&self)->usize {
+        self.pos.index()
+    }
+    fn source_pos(&self)->SourcePos {
+        self.pos
+    }
+    fn message(&self)->String {
+        self.message.clone()
+    }
+    fn unexpected(span:Span, item:String)->DomainError {
+        DomainError{pos:span.start(), code:Code::Reserved, message:format!("{} is reserved", item)}
+    }
+}
+
+impl From<ParseError> for DomainError {
+    fn from(err:ParseError)->DomainError {
+        DomainError{pos:err.source_pos(), code:Code::Syntax, message:err.message()}
+    }
+}
+
+#[test]
+fn custom_error_test_0() {
+    let digit = SatisfyMap::<char, char, DomainError>::new(String::from("digit"),
+        Arc::new(|c:&char| if c.is_digit(10) { Some(*c) } else { None }));
+    let byte = parser(Arc::new(many1(Arc::new(digit)))).bind(Arc::new(Box::new(
+        |state:&mut State<char>, digits:Vec<char>| -> Status<u8, DomainError> {
+            let text = digits.into_iter().collect::<String>();
+            text.parse::<u8>().map_err(|_| DomainError{pos:state.source_pos(), code:Code::Overflow,
+                                                       message:format!("{} is out of byte", text)})
+        })));
+    assert_eq!(byte(&mut TextState::new("255")).unwrap(), 255);
+    let err = byte(&mut TextState::new("300")).unwrap_err();
+    assert_eq!(err.code, Code::Overflow);
+    assert_eq!(err.pos(), 3);
+    let err = byte(&mut TextState::new("x")).unwrap_err();
+    assert_eq!(err.code, Code::Syntax);
+    assert_eq!(err.message(), "unexpected 'x', expected digit");
+}
+
+#[test]
+fn custom_error_test_1() {
+    let p = either(Arc::new(Equal::<char, SimpleError>::new('a')), Arc::new(Equal::new('b')));
+    let err:SimpleError = p(&mut TextState::new("c")).unwrap_err();
+    assert_eq!(err.pos(), 0);
+    assert_eq!(err.message(), "unexpected 'c', expected 'b'");
+}
+
+// key=digit; entries, a broken entry is skipped until its ';'
+fn entries() -> Monad<'static, char, Vec<Option<(char, char)>>, Vec<Option<(char, char)>>> {
+    let entry = alpha().bind(Arc::new(Box::new(|state:&mut State<char>, key:char| -> Status<(char, char)> {
+        let value = try!(between(Arc::new(eq('=')), Arc::new(digit()), Arc::new(eq(';')))(state));
+        Ok((key, value))
+    })));
+    let item = recover_with(Arc::new(entry), Arc::new(skip_until(Arc::new(eq(';')))));
+    parser(Arc::new(many(Arc::new(item)))).over(Arc::new(eof()))
+}
+
+#[test]
+fn recover_test_0() {
+    let mut state = TextState::new("a=1;b=?;c=3;d=x");
+    let (value, errors) = parse_recover(Arc::new(entries()), &mut state, None);
+    assert_eq!(value, Some(vec![Some(('a', '1')), None, Some(('c', '3')), None]));
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0].pos(), 6);
+    assert_eq!(errors[0].message(), "unexpected '?', expected digit");
+    assert_eq!(errors[1].pos(), 14);
+    // errors are moved out of the state
+    assert!(collected_errors::<char, SimpleError>(&state).is_empty());
+}
+
+#[test]
+fn recover_test_1() {
+    // two errors are collected, the third one is past the limit and fails the whole parse
+    let mut state = TextState::new("a=?;b=?;c=?;d=4;");
+    let (value, errors) = parse_recover(Arc::new(entries()), &mut state, Some(2));
+    assert_eq!(value, None);
+    assert_eq!(errors.len(), 3);
+    assert_eq!(errors[0].pos(), 2);
+    assert_eq!(errors[1].pos(), 6);
+    assert_eq!(errors[2].pos(), 10);
+    assert_eq!(errors[2].message(), "unexpected '?', expected digit");
+    // recovered errors in a branch rolled back are dropped
+    let mut state = TextState::new("a=?;");
+    let p = try(Arc::new(parser(Arc::new(entries())).then(Arc::new(eq('!')))));
+    assert!(p(&mut state).is_err());
+    assert!(collected_errors::<char, SimpleError>(&state).is_empty());
+}
+
+#[test]
+fn recover_test_2() {
+    // an entry is committed after '=', its broken value fails the parse instead of being skipped
+    let entry = alpha().then(Arc::new(eq('='))).then(Arc::new(digit().cut())).over(Arc::new(eq(';')));
+    let item = recover_with(Arc::new(entry), Arc::new(skip_until(Arc::new(eq(';')))));
+    let (value, errors) = parse_recover(Arc::new(many(Arc::new(item))), &mut TextState::new("a=1;b=?;c=3;"), None);
+    assert_eq!(value, None);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].pos(), 6);
+    assert!(errors[0].is_fatal());
+}
+
+#[test]
+fn reply_test_0() {
+    let mut state = TextState::new("ab");
+    assert_eq!(eq('a').reply(&mut state), Reply::Consumed(Ok('a')));
+    assert!(!eq('x').reply(&mut state).is_consumed());
+    let ab = Arc::new(eq('a').then(Arc::new(eq('c'))));
+    let mut state = TextState::new("ab");
+    assert!(ab.reply(&mut state).is_consumed());
+    // try turns a consumed error into an empty one
+    let mut state = TextState::new("ab");
+    let reply = try(ab.clone()).reply(&mut state);
+    assert!(!reply.is_consumed());
+    assert_eq!(state.pos(), 0);
+    // either doesn't try y after x consumed
+    let mut state = TextState::new("ab");
+    let err = either(ab.clone(), Arc::new(eq('a').then(Arc::new(eq('b')))))(&mut state).unwrap_err();
+    assert_eq!(err.message(), "unexpected 'b', expected 'c'");
+    let mut state = TextState::new("ab");
+    let re = either(Arc::new(try(ab.clone())), Arc::new(eq('a').then(Arc::new(eq('b')))))(&mut state);
+    assert_eq!(re, Ok('b'));
+}
+
+#[test]
+fn reply_test_1() {
+    // many fails if p fails after consuming input
+    let mut state = TextState::new("ababac");
+    let ab = Arc::new(eq('a').then(Arc::new(eq('b'))));
+    let err = many(ab.clone())(&mut state).unwrap_err();
+    assert_eq!(err.pos(), 5);
+    let mut state = TextState::new("ababac");
+    assert_eq!(many(Arc::new(try(ab.clone())))(&mut state), Ok(vec!['b', 'b']));
+    assert_eq!(state.pos(), 4);
+    // label only replaces errors of empty replies
+    let mut state = TextState::new("ac");
+    let err = label(ab.clone(), String::from("ab"))(&mut state).unwrap_err();
+    assert_eq!(err.message(), "unexpected 'c', expected 'b'");
+}
+
+#[test]
+fn context_test_0() {
+    // k:[#digit#digit...] in an object
+    let element = Arc::new(eq_with('#').then(Arc::new(digit_with())).context(String::from("in array element")));
+    let array = between(Arc::new(eq_with('[')), Arc::new(many1(element)), Arc::new(eq_with(']')));
+    let key = eq_with('k').then(Arc::new(eq_with(':'))).then(Arc::new(array)).context(String::from("in key 'k'"));
+    let object = context(Arc::new(key), String::from("in object"));
+    let err:ParseError = object(&mut TextState::new("k:[#1#x]")).unwrap_err();
+    assert_eq!(err.contexts(), &[String::from("in object"), String::from("in key 'k'"),
+                                 String::from("in array element")]);
+    assert_eq!(err.message(), "in object > in key 'k' > in array element: unexpected 'x', expected digit");
+    let err = object(&mut TextState::new("k;")).unwrap_err();
+    assert_eq!(err.message(), "in object > in key 'k': unexpected ';', expected ':'");
+    assert!(object(&mut TextState::new("k:[#1#2]")).is_ok());
+}
+
+#[test]
+fn context_test_1() {
+    // alternatives failed at the same position keep no frame only one of them pushed
+    let a = Arc::new(eq_with::<_, ParseError>('a').context(String::from("in a")));
+    let b = Arc::new(eq_with('b').context(String::from("in b")));
+    let err = either(a.clone(), b.clone()).parse(&mut TextState::new("c")).unwrap_err();
+    assert!(err.contexts().is_empty());
+    assert_eq!(err.message(), "unexpected 'c', expected 'a' or 'b'");
+    let err = either(b.clone(), a.clone()).parse(&mut TextState::new("c")).unwrap_err();
+    assert!(err.contexts().is_empty());
+    // the same frame on both is kept
+    let a = Arc::new(eq_with::<_, ParseError>('a').context(String::from("in letter")));
+    let b = Arc::new(eq_with('b').context(String::from("in letter")));
+    let err = either(a, b).parse(&mut TextState::new("c")).unwrap_err();
+    assert_eq!(err.message(), "in letter: unexpected 'c', expected 'a' or 'b'");
+}
+
+#[test]
+fn context_test_2() {
+    // frames go in front of the message of the default error
+    let element = Arc::new(eq('#').then(Arc::new(digit())).context(String::from("in array element")));
+    let array = eq('[').then(Arc::new(many1(element))).context(String::from("in array"));
+    let err = array(&mut TextState::new("[#1#x")).unwrap_err();
+    assert_eq!(err.message(), "in array > in array element: unexpected 'x', expected digit");
+    let a = Arc::new(eq('a').context(String::from("in a")));
+    let b = Arc::new(eq('b').context(String::from("in b")));
+    let err = either(a, b).parse(&mut TextState::new("c")).unwrap_err();
+    assert_eq!(err.message(), "unexpected 'c', expected 'b'");
+}
+
+#[test]
+fn cut_test_0() {
+    let item = Arc::new(eq('#').then(Arc::new(digit())));
+    let list = sep_by(Arc::new(eq(',')), item.clone()).over(Arc::new(eof()));
+    assert_eq!(list(&mut TextState::new("#1,#2")), Ok(vec!['1', '2']));
+    // without cut the broken element just ends the list
+    let err = list(&mut TextState::new("#1,#x")).unwrap_err();
+    assert_eq!(err.pos(), 2);
+    assert_eq!(err.message(), "unexpected ',', expected end of input");
+    // after '#' the element is committed
+    let item = Arc::new(eq('#').then(Arc::new(cut(Arc::new(digit())))));
+    let list = sep_by(Arc::new(eq(',')), item.clone()).over(Arc::new(eof()));
+    let err = list(&mut TextState::new("#1,#x")).unwrap_err();
+    assert_eq!(err.pos(), 4);
+    assert!(err.is_fatal());
+    assert_eq!(err.message(), "unexpected 'x', expected digit");
+}
+
+#[test]
+fn cut_test_1() {
+    // either doesn't try the next alternative after a fatal error, even an empty one
+    let p = either(Arc::new(digit().cut()), Arc::new(eq('x')));
+    let mut state = TextState::new("x");
+    assert!(p(&mut state).unwrap_err().is_fatal());
+    let p = many(Arc::new(try(Arc::new(eq('a').then(Arc::new(cut(Arc::new(eq('b')))))))));
+    let mut state = TextState::new("abac");
+    assert_eq!(p(&mut state).unwrap_err().pos(), 3);
+    assert_eq!(state.pos(), 3);
+}
+
+#[test]
+fn cut_test_2() {
+    // the default error is fatal after a cut too, so a committed item doesn't end a list early
+    let item = Arc::new(eq('#').then(Arc::new(digit())));
+    let list = many(Arc::new(try(item.clone()))).over(Arc::new(eof()));
+    assert_eq!(list(&mut TextState::new("#1#x")).unwrap_err().pos(), 2);
+    let item = Arc::new(eq('#').then(Arc::new(digit().cut())));
+    let list = many(Arc::new(try(item.clone()))).over(Arc::new(eof()));
+    let err = list(&mut TextState::new("#1#x")).unwrap_err();
+    assert_eq!(err.pos(), 3);
+    assert!(err.is_fatal());
+    assert!(ParseError::from(err).is_fatal());
+}
+
+#[test]
+fn farthest_failure_test_0() {
+    let ab = Arc::new(eq('a').then(Arc::new(eq('b'))));
+    let p = Arc::new(many(Arc::new(try(ab.clone()))).over(Arc::new(eof())));
+    let mut state = TextState::new("ababac");
+    let err = p(&mut state).unwrap_err();
+    assert_eq!(err.pos(), 4);
+    // try backtracked over the real divergence, run reports it
+    let mut state = TextState::new("ababac");
+    let err = run(p.clone(), &mut state).unwrap_err();
+    assert_eq!(err.pos(), 5);
+    assert_eq!(err.message(), "unexpected 'c', expected 'b'");
+    assert_eq!(state.farthest_failure().map(SimpleError::from), Some(err));
+    let mut state = TextState::new("abab");
+    assert_eq!(run(p.clone(), &mut state), Ok(vec!['b', 'b']));
+}
+
+#[test]
+fn farthest_failure_test_2() {
+    // frames and labels around the farthest failure are kept
+    let ab = Arc::new(eq_with::<_, ParseError>('a').then(Arc::new(eq_with('b'))));
+    let p = Arc::new(many(Arc::new(try(ab.clone()))).over(Arc::new(eof_with())).context(String::from("in list")));
+    let err = run(p, &mut TextState::new("ababac")).unwrap_err();
+    assert_eq!(err.pos(), 5);
+    assert_eq!(err.message(), "in list: unexpected 'c', expected 'b'");
+    let p = Arc::new(label(Arc::new(eq_with::<_, ParseError>('a')), String::from("letter a")));
+    let mut state = TextState::new("x");
+    assert!(run(p, &mut state).is_err());
+    assert_eq!(state.farthest_failure().unwrap().expected(), &[String::from("letter a")]);
+    // a frame outside the parser which met the farthest failure is not added
+    let p = Arc::new(try(ab.clone()).context(String::from("in pair")).then(Arc::new(eq_with('z'))));
+    let mut state = TextState::new("ab");
+    assert!(run(p, &mut state).is_err());
+    assert!(state.farthest_failure().unwrap().contexts().is_empty());
+}
+
+#[test]
+fn farthest_failure_test_1() {
+    // failures at the same farthest position merge their expectations
+    let p = either(Arc::new(try(Arc::new(eq_with::<_, ParseError>('a').then(Arc::new(eq_with('b')))))),
+                   Arc::new(try(Arc::new(eq_with('a').then(Arc::new(eq_with('c')))))));
+    let p = Arc::new(either(Arc::new(p), Arc::new(pack_with('z'))).over(Arc::new(eof_with())));
+    let err = run(p, &mut TextState::new("ax")).unwrap_err();
+    assert_eq!(err.pos(), 1);
+    assert_eq!(err.expected(), &[String::from("'b'"), String::from("'c'")]);
+}
+
+#[test]
+fn farthest_failure_test_3() {
+    // a misused combinator is a failure as any other, even when an alternative hides it
+    let empty = Arc::new(pack_with::<char, (), ParseError>(()));
+    let p = Arc::new(either(Arc::new(many(empty.clone())), Arc::new(pack_with(Vec::new()))));
+    let mut state = TextState::new("");
+    assert_eq!(run(p, &mut state), Ok(Vec::new()));
+    assert_eq!(state.farthest_failure().unwrap().message(), "many is applied to a parser that accepts empty input");
+    let p = Arc::new(either(Arc::new(skip_many(empty.clone())), Arc::new(pack_with(Vec::new()))));
+    let mut state = TextState::new("");
+    assert_eq!(run(p, &mut state), Ok(Vec::new()));
+    assert_eq!(state.farthest_failure().unwrap().message(), "skip_many is applied to a parser that accepts empty input");
+}
+
+#[test]
+fn diagnostic_test_0() {
+    let source = "ab\r\ncd\nx\u{1F600}z";
+    assert_eq!(Position::of(source, 4), Position::new(1, 0));
+    assert_eq!(Position::of(source, 12), Position::new(2, 3));
+    let p = Arc::new(eq_with::<_, ParseError>('a').then(Arc::new(eq_with('b'))).context(String::from("pair")));
+    let err = run(p, &mut TextState::new("ac")).unwrap_err();
+    let diag = Diagnostic::from_parse_error("ac", &err);
+    assert_eq!(diag.range().start(), Position::new(0, 1));
+    assert_eq!(diag.severity(), Severity::Error);
+    assert_eq!(diag.unexpected(), Some("'c'"));
+    assert_eq!(diag.expected(), &[String::from("'b'")]);
+    assert_eq!(diag.contexts(), &[String::from("pair")]);
+}
+
+#[test]
+fn diagnostic_test_1() {
+    let err = ParseError::new(0, String::from("say \"hi\"\n"));
+    let diag = Diagnostic::new("abc", &err).with_severity(Severity::Warning);
+    assert_eq!(diagnostic::to_json(&[diag]),
+               "[{\"range\":{\"start\":{\"line\":0,\"character\":0},\"end\":{\"line\":0,\"character\":0}},\
+                \"severity\":2,\"source\":\"ruskell\",\"message\":\"say \\\"hi\\\"\\n\",\
+                \"data\":{\"unexpected\":null,\"expected\":[],\"contexts\":[]}}]");
+}
+
+fn arithmetic() -> Arc<Parsec<char, i64>> {
+    let number = parser(Arc::new(uinteger())).bind(Arc::new(Box::new(|_:&mut State<char>, x:String|->Status<i64> {
+        Ok(x.parse::<i64>().unwrap())
+    })));
+    let table = vec![
+        vec![Operator::prefix(Arc::new(eq('-')), |x:i64| -x)],
+        vec![Operator::postfix(Arc::new(eq('!')), |x:i64| (1..x+1).product())],
+        vec![Operator::infix(Arc::new(eq('^')), |x:i64, y:i64| x.pow(y as u32), Assoc::Right)],
+        vec![Operator::infix(Arc::new(eq('*')), |x:i64, y:i64| x * y, Assoc::Left),
+             Operator::infix(Arc::new(eq('/')), |x:i64, y:i64| x / y, Assoc::Left)],
+        vec![Operator::infix(Arc::new(eq('+')), |x:i64, y:i64| x + y, Assoc::Left),
+             Operator::infix(Arc::new(eq('-')), |x:i64, y:i64| x - y, Assoc::Left)],
+        vec![Operator::infix(Arc::new(eq('<')), |x:i64, y:i64| (x < y) as i64, Assoc::None)],
+    ];
+    Arc::new(expression(table, Arc::new(number)).over(Arc::new(eof())))
+}
+
+#[test]
+fn expression_test_0() {
+    let p = arithmetic();
+    assert_eq!(p.parse(&mut TextState::new("1+2*3")), Ok(7));
+    assert_eq!(p.parse(&mut TextState::new("10-4-3")), Ok(3));
+    assert_eq!(p.parse(&mut TextState::new("2^3^2")), Ok(512));
+    assert_eq!(p.parse(&mut TextState::new("-2*3!+20/4")), Ok(-7));
+    assert_eq!(p.parse(&mut TextState::new("1+1<3")), Ok(1));
+}
+
+#[test]
+fn expression_test_1() {
+    let p = arithmetic();
+    let err = p.parse(&mut TextState::new("1<2<3")).unwrap_err();
+    assert_eq!(err.pos(), 3);
+    assert_eq!(err.message(), "ambiguous use of a non associative operator");
+    let err = p.parse(&mut TextState::new("1+*2")).unwrap_err();
+    assert_eq!(err.pos(), 2);
+}
+
+#[test]
+fn expression_test_2() {
+    // operator functions may borrow from the caller, as the parsers may
+    let names = vec![String::from("x"), String::from("y")];
+    let name = Arc::new(alpha().bind(Arc::new(Box::new(|_:&mut State<char>, c:char|->Status<&str> {
+        Ok(names.iter().find(|n| n.starts_with(c)).map_or("?", |n| n.as_str()))
+    }))));
+    let fallback = names[0].as_str();
+    let table = vec![
+        vec![Operator::infix(Arc::new(eq('|')), |x:&str, y:&str| if x == "?" { y } else { x }, Assoc::Left)],
+        vec![Operator::prefix(Arc::new(eq('!')), |_:&str| fallback)],
+    ];
+    let p = expression(table, name.clone()).over(Arc::new(eof()));
+    assert_eq!(p.parse(&mut TextState::new("z|y")), Ok("y"));
+    assert_eq!(p.parse(&mut TextState::new("!z|y")), Ok("x"));
+    let expr:Pratt<char, &str> = pratt();
+    expr.nud(name);
+    expr.infix(Arc::new(eq('|')), 10, 11, |x:&str, y:&str| if x == "?" { y } else { x });
+    expr.postfix(Arc::new(eq('!')), 20, |_:&str| fallback);
+    assert_eq!(expr.parse(&mut TextState::new("z!|y")), Ok("x"));
+}
+
+#[test]
+fn chain_test_0() {
+    let number = Arc::new(parser(Arc::new(uinteger_with())).bind(Arc::new(Box::new(|_:&mut State<char>, x:String|->Status<i64, ParseError> {
+        Ok(x.parse::<i64>().unwrap())
+    }))));
+    let sub:Binary<i64> = Arc::new(Box::new(|x, y| x - y));
+    let minus = Arc::new(eq_with('-').bind(Arc::new(Box::new(move |_:&mut State<char>, _:char| Ok(sub.clone())))));
+    let left = chainl1(number.clone(), minus.clone());
+    assert_eq!(left.parse(&mut TextState::new("10-4-3")), Ok(3));
+    let right = chainr1(number.clone(), minus.clone());
+    assert_eq!(right.parse(&mut TextState::new("10-4-3")), Ok(9));
+    assert_eq!(right.parse(&mut TextState::new("10")), Ok(10));
+    // an operator without its right operand is an error
+    let err = left.parse(&mut TextState::new("10-")).unwrap_err();
+    assert_eq!(err.pos(), 3);
+    // the digit and the operator which could continue the chain are expected too
+    let whole = parser(Arc::new(left.clone())).over(Arc::new(eof_with()));
+    let err = whole.parse(&mut TextState::new("10-4x")).unwrap_err();
+    assert_eq!(err.message(), "unexpected 'x', expected digit, '-' or end of input");
+    // juxtaposition, an operator matching nothing ends the chain where no operand follows it
+    let mul:Binary<i64> = Arc::new(Box::new(|x, y| x * y));
+    let juxtapose = Arc::new(pack_with::<char, (), _>(()).bind(Arc::new(Box::new(move |_:&mut State<char>, _:()| Ok(mul.clone())))));
+    let operand = Arc::new(digit_with().bind(Arc::new(Box::new(|_:&mut State<char>, x:char|->Status<i64, ParseError> {
+        Ok(x.to_digit(10).unwrap() as i64)
+    }))));
+    let mut state = TextState::new("234x");
+    assert_eq!(chainl1(operand.clone(), juxtapose.clone()).parse(&mut state), Ok(24));
+    assert_eq!(state.pos(), 3);
+    assert_eq!(chainr1(operand.clone(), juxtapose.clone()).parse(&mut TextState::new("23")), Ok(6));
+    let whole = parser(Arc::new(chainl1(operand.clone(), juxtapose.clone()))).over(Arc::new(eof_with()));
+    let err = whole.parse(&mut TextState::new("23x")).unwrap_err();
+    assert_eq!(err.message(), "unexpected 'x', expected digit or end of input");
+}
+
+fn pratt_grammar() -> Pratt<'static, char, String> {
+    let expr:Pratt<char, String> = pratt();
+    expr.nud(Arc::new(alpha().bind(Arc::new(Box::new(|_:&mut State<char>, x:char|->Status<String> {
+        Ok(x.to_string())
+    })))));
+    expr.nud(Arc::new(between(Arc::new(eq('(')), Arc::new(expr.min_power(0)), Arc::new(eq(')')))));
+    expr.prefix(Arc::new(eq('-')), 70, |x| format!("(- {})", x));
+    expr.infix(Arc::new(eq('+')), 10, 11, |x, y| format!("(+ {} {})", x, y));
+    expr.infix(Arc::new(eq('*')), 20, 21, |x, y| format!("(* {} {})", x, y));
+    expr.infix(Arc::new(eq('^')), 31, 30, |x, y| format!("(^ {} {})", x, y));
+    expr.postfix(Arc::new(eq('!')), 60, |x| format!("(! {})", x));
+    // a ? b : c, right associative
+    let (then, otherwise) = (expr.min_power(0), expr.min_power(4));
+    expr.led(5, Arc::new(eq('?').bind(Arc::new(Box::new(move |state:&mut State<char>, _:char|->Status<Unary<String>> {
+        let y = try!(then.parse(state));
+        try!(eq(':').parse(state));
+        let z = try!(otherwise.parse(state));
+        Ok(Arc::new(Box::new(move |x| format!("(? {} {} {})", x, y, z))))
+    })))));
+    let inner = expr.min_power(0);
+    expr.led(80, Arc::new(eq('[').bind(Arc::new(Box::new(move |state:&mut State<char>, _:char|->Status<Unary<String>> {
+        let i = try!(inner.parse(state));
+        try!(eq(']').parse(state));
+        Ok(Arc::new(Box::new(move |x| format!("([] {} {})", x, i))))
+    })))));
+    // f(a, b), binds tighter than any prefix
+    let args = expr.min_power(0);
+    expr.led(90, Arc::new(eq('(').bind(Arc::new(Box::new(move |state:&mut State<char>, _:char|->Status<Unary<String>> {
+        let xs = try!(sep_by(Arc::new(eq(',')), Arc::new(args.clone())).parse(state));
+        try!(eq(')').parse(state));
+        Ok(Arc::new(Box::new(move |f| {
+            let mut items = vec![f];
+            items.extend(xs.iter().cloned());
+            format!("(call {})", items.join(" "))
+        })))
+    })))));
+    expr
+}
+
+#[test]
+fn pratt_test_0() {
+    let expr = pratt_grammar();
+    let p = Arc::new(expr.over(Arc::new(eof())));
+    assert_eq!(p.parse(&mut TextState::new("a+b*c")), Ok(String::from("(+ a (* b c))")));
+    assert_eq!(p.parse(&mut TextState::new("a*b+c")), Ok(String::from("(+ (* a b) c)")));
+    assert_eq!(p.parse(&mut TextState::new("a^b^c")), Ok(String::from("(^ a (^ b c))")));
+    assert_eq!(p.parse(&mut TextState::new("-a!")), Ok(String::from("(! (- a))")));
+    assert_eq!(p.parse(&mut TextState::new("(a+b)*c")), Ok(String::from("(* (+ a b) c)")));
+    assert_eq!(p.parse(&mut TextState::new("a?b:c?d:e")), Ok(String::from("(? a b (? c d e))")));
+    assert_eq!(p.parse(&mut TextState::new("a[b+c][d]")), Ok(String::from("([] ([] a (+ b c)) d)")));
+    assert_eq!(p.parse(&mut TextState::new("f(a,b+c)")), Ok(String::from("(call f a (+ b c))")));
+    assert_eq!(p.parse(&mut TextState::new("f()(x)")), Ok(String::from("(call (call f) x)")));
+    assert_eq!(p.parse(&mut TextState::new("-f(x)*(a)")), Ok(String::from("(* (- (call f x)) a)")));
+}
+
+#[test]
+fn pratt_test_1() {
+    let p = Arc::new(pratt_grammar().over(Arc::new(eof())));
+    let err = p.parse(&mut TextState::new("a+*b")).unwrap_err();
+    assert_eq!(err.pos(), 2);
+    let err = p.parse(&mut TextState::new("a[b")).unwrap_err();
+    assert_eq!(err.pos(), 3);
+    // sep_by backtracks over the dangling ',', the call wants its ')' there
+    let err = p.parse(&mut TextState::new("f(a,")).unwrap_err();
+    assert_eq!(err.pos(), 3);
+    // handlers hold operands only, dropping the parser frees them
+    let expr = pratt_grammar();
+    let operand = expr.min_power(0);
+    assert_eq!(operand.parse(&mut TextState::new("a")), Ok(String::from("a")));
+    drop(expr);
+    let mut state = TextState::new("a");
+    assert!(operand.parse(&mut state).is_err());
+    assert_eq!(state.farthest_failure().unwrap().message(), "pratt parser is dropped");
+
+    // handlers can be registered while parsing
+    let expr = pratt_grammar();
+    let rules = expr.clone();
+    expr.nud(Arc::new(eq('#').bind(Arc::new(Box::new(move |_:&mut State<char>, _:char|->Status<String> {
+        rules.postfix(Arc::new(eq('%')), 60, |x| format!("(% {})", x));
+        Ok(String::from("#"))
+    })))));
+    assert_eq!(expr.parse(&mut TextState::new("#")), Ok(String::from("#")));
+    assert_eq!(expr.parse(&mut TextState::new("#%")), Ok(String::from("(% #)")));
+    // a led matching nothing would match again forever
+    let expr = pratt_grammar();
+    expr.led(1, Arc::new(pack::<char, ()>(()).bind(Arc::new(Box::new(|_:&mut State<char>, _:()|->Status<Unary<String>> {
+        Ok(Arc::new(Box::new(|x| x)))
+    })))));
+    let mut state = TextState::new("a");
+    let err = expr.parse(&mut state).unwrap_err();
+    assert_eq!(err.message(), "pratt led handler accepts empty input");
+    // the farthest failure merges it with what the other leds expected there
+    assert!(state.farthest_failure().unwrap().message().ends_with(", pratt led handler accepts empty input"));
+}
+
+#[test]
+fn repeat_test_0() {
+    let hex = Arc::new(one_of_with::<_, ParseError>(&"0123456789abcdef".chars().collect::<Vec<char>>()));
+    let p = count(4, hex.clone());
+    assert_eq!(p.parse(&mut TextState::new("00ff9")), Ok(vec!['0', '0', 'f', 'f']));
+    let err = p.parse(&mut TextState::new("0fz")).unwrap_err();
+    assert_eq!(err.pos(), 2);
+    assert_eq!(err.messages(), &[String::from("expected 4 items, found 2")]);
+    assert!(err.contexts().is_empty());
+
+    let digits = Arc::new(digit_with::<ParseError>());
+    let mut state = TextState::new("12345");
+    assert_eq!(many_m_n(2, 3, digits.clone()).parse(&mut state), Ok(vec!['1', '2', '3']));
+    assert_eq!(at_most(3, digits.clone()).parse(&mut state), Ok(vec!['4', '5']));
+    let err = at_least(2, digits.clone()).parse(&mut TextState::new("1")).unwrap_err();
+    assert_eq!(err.message(), "unexpected end of input, expected digit, expected at least 2 items, found 1");
+    let err = many_m_n(2, 3, digits.clone()).parse(&mut TextState::new("")).unwrap_err();
+    assert_eq!(err.messages(), &[String::from("expected 2 to 3 items, found 0")]);
+}
+
+#[test]
+fn repeat_test_1() {
+    let mut state = TextState::new("aaaab");
+    assert_eq!(skip_count(2, Arc::new(eq('a'))).parse(&mut state), Ok(Vec::new()));
+    assert_eq!(skip_at_most(5, Arc::new(eq('a'))).parse(&mut state), Ok(Vec::new()));
+    assert_eq!(eq('b').parse(&mut state), Ok('b'));
+
+    // an item is expected next only if the repeat stopped before max
+    let p = parser(Arc::new(at_most(3, Arc::new(eq_with::<_, ParseError>('a'))))).then(Arc::new(eq_with('b')));
+    let err = p.parse(&mut TextState::new("aaa")).unwrap_err();
+    assert_eq!(err.message(), "unexpected end of input, expected 'b'");
+    let err = p.parse(&mut TextState::new("aax")).unwrap_err();
+    assert_eq!(err.message(), "unexpected 'x', expected 'a' or 'b'");
+    let mut state = TextState::new("aaa");
+    let err = many_m_n(3, 2, Arc::new(eq('a'))).parse(&mut state).unwrap_err();
+    assert_eq!(err.pos(), 0);
+    assert_eq!(state.pos(), 0);
+}
+
+#[test]
+fn repeat_test_2() {
+    // many tells what else could come where it stopped, as many_m_n does
+    let p = many(Arc::new(digit_with::<ParseError>())).then(Arc::new(eq_with(';')));
+    let err = p.parse(&mut TextState::new("12x")).unwrap_err();
+    assert_eq!(err.message(), "unexpected 'x', expected digit or ';'");
+    let q = at_least(0, Arc::new(digit_with::<ParseError>())).then(Arc::new(eq_with(';')));
+    assert_eq!(q.parse(&mut TextState::new("12x")).unwrap_err(), err);
+    let p = skip_many(Arc::new(digit_with::<ParseError>())).then(Arc::new(eq_with(';')));
+    assert_eq!(p.parse(&mut TextState::new("12x")).unwrap_err(), err);
+    let list = sep_by(Arc::new(eq_with::<_, ParseError>(',')), Arc::new(digit_with())).then(Arc::new(eq_with(']')));
+    let err = list.parse(&mut TextState::new("1,2x")).unwrap_err();
+    assert_eq!(err.message(), "unexpected 'x', expected ',' or ']'");
+    let err = list.parse(&mut TextState::new("x")).unwrap_err();
+    assert_eq!(err.message(), "unexpected 'x', expected digit or ']'");
+}
+
+#[test]
+fn sep_end_by_test_0() {
+    let comma = Arc::new(eq_with::<_, ParseError>(','));
+    let digits = Arc::new(digit_with());
+    assert_eq!(sep_end_by(comma.clone(), digits.clone()).parse(&mut TextState::new("1,2,")), Ok(vec!['1', '2']));
+    assert_eq!(sep_end_by(comma.clone(), digits.clone()).parse(&mut TextState::new("1,2")), Ok(vec!['1', '2']));
+    assert_eq!(sep_end_by(comma.clone(), digits.clone()).parse(&mut TextState::new("x")), Ok(Vec::new()));
+    assert!(sep_end_by1(comma.clone(), digits.clone()).parse(&mut TextState::new("x")).is_err());
+    let semi = Arc::new(eq_with(';'));
+    assert_eq!(end_by(semi.clone(), digits.clone()).parse(&mut TextState::new("1;2;")), Ok(vec!['1', '2']));
+    assert!(end_by1(semi.clone(), digits.clone()).parse(&mut TextState::new("1;2")).is_err());
+    let list = parser(Arc::new(sep_end_by(comma.clone(), digits.clone()))).over(Arc::new(eq_with(']')));
+    let err = list.parse(&mut TextState::new("1,2x")).unwrap_err();
+    assert_eq!(err.message(), "unexpected 'x', expected ',' or ']'");
+    let err = list.parse(&mut TextState::new("1,x")).unwrap_err();
+    assert_eq!(err.message(), "unexpected 'x', expected digit or ']'");
+}
+
+#[test]
+fn delimited_list_test_0() {
+    let list = delimited_list(Arc::new(eq('[')), Arc::new(eq(',')), Arc::new(digit()), Arc::new(eq(']')));
+    assert_eq!(list.parse(&mut TextState::new("[]")), Ok(Vec::new()));
+    assert_eq!(list.parse(&mut TextState::new("[1,2,3,]")), Ok(vec!['1', '2', '3']));
+    assert_eq!(list.parse(&mut TextState::new("[1,2]")), Ok(vec!['1', '2']));
+    let err = list.parse(&mut TextState::new("[1,,]")).unwrap_err();
+    assert_eq!(err.pos(), 3);
+}
+
+#[test]
+fn optional_test_0() {
+    let sign = Arc::new(eq('-'));
+    let mut state = TextState::new("-1");
+    assert_eq!(optional(sign.clone()).parse(&mut state), Ok(Some('-')));
+    assert_eq!(optional(sign.clone()).parse(&mut state), Ok(None));
+    assert_eq!(option('+', sign.clone()).parse(&mut state), Ok('+'));
+    assert_eq!(digit().parse(&mut state), Ok('1'));
+    // an error after consuming input is not optional
+    let pair = eq('a').then(Arc::new(eq('b')));
+    assert!(pair.clone().optional().parse(&mut TextState::new("ac")).is_err());
+    let p = eq_with::<_, ParseError>('x').optional_skip().then(Arc::new(eq_with('y')));
+    assert_eq!(p.parse(&mut TextState::new("xy")), Ok('y'));
+    assert_eq!(p.parse(&mut TextState::new("y")), Ok('y'));
+    assert_eq!(eq('x').option('z').parse(&mut TextState::new("y")), Ok('z'));
+    // the dropped error tells what else could come at the failing position
+    let err = p.parse(&mut TextState::new("z")).unwrap_err();
+    assert_eq!(err.message(), "unexpected 'z', expected 'x' or 'y'");
+    let err = p.parse(&mut TextState::new("xz")).unwrap_err();
+    assert_eq!(err.message(), "unexpected 'z', expected 'y'");
+    // dropped errors are kept at the token index, the rollback of the second optional keeps them
+    let p = token_with::<_, ParseError>(Tok::Num(1)).then(Arc::new(token_with(Tok::Plus).optional_skip()))
+        .then(Arc::new(token_with(Tok::Num(2)).optional_skip())).then(Arc::new(eof_with()));
+    let err = p.parse(&mut lex("1 x")).unwrap_err();
+    assert_eq!(err.message(), "unexpected Ident(\"x\"), expected Plus, Num(2) or end of input");
+}
+
+#[test]
+fn look_ahead_test_0() {
+    let mut state = TextState::new("ab");
+    assert_eq!(look_ahead(Arc::new(eq('a').then(Arc::new(eq('b'))))).parse(&mut state), Ok('b'));
+    assert_eq!(state.pos(), 0);
+    assert!(look_ahead(Arc::new(eq('b'))).parse(&mut state).is_err());
+    // p failing after consuming is not rolled back, unless p backtracks itself
+    let mut bad = TextState::new("ax");
+    let ab = Arc::new(eq('a').then(Arc::new(eq('b'))));
+    assert!(look_ahead(ab.clone()).reply(&mut bad).is_consumed());
+    assert_eq!(bad.pos(), 1);
+    let mut bad = TextState::new("ax");
+    assert!(!look_ahead(Arc::new(try(ab.clone()))).reply(&mut bad).is_consumed());
+    assert_eq!(bad.pos(), 0);
+    assert_eq!(eq('a').then(Arc::new(eq('b'))).parse(&mut state), Ok('b'));
+    // at the end of input
+    assert!(look_ahead(Arc::new(one())).parse(&mut state).is_err());
+    assert_eq!(look_ahead(Arc::new(eof())).parse(&mut state), Ok(()));
+    assert_eq!(state.pos(), 2);
+}
+
+#[test]
+fn not_followed_by_test_0() {
+    let keyword = eq_with::<_, ParseError>('i').then(Arc::new(eq_with('f'))).over(Arc::new(not_followed_by(Arc::new(alpha_with()))));
+    let mut state = TextState::new("if x");
+    assert_eq!(keyword.parse(&mut state), Ok('f'));
+    assert_eq!(state.pos(), 2);
+    let err = keyword.parse(&mut TextState::new("iffy")).unwrap_err();
+    assert_eq!(err.pos(), 2);
+    assert_eq!(err.unexpected(), Some("'f'"));
+    let mut state = TextState::new("if");
+    assert_eq!(keyword.parse(&mut state), Ok('f'));
+    assert_eq!(not_followed_by(Arc::new(one())).parse(&mut state), Ok(()));
+    assert!(not_followed_by(Arc::new(eof())).parse(&mut state).is_err());
+    assert_eq!(state.pos(), 2);
+}
+
+#[test]
+fn not_followed_by_test_1() {
+    // p failing farther than where parsing stops is not the farthest failure
+    let ab = Arc::new(eq_with::<_, ParseError>('a').then(Arc::new(eq_with('b'))));
+    let p = Arc::new(not_followed_by(ab.clone()).then(Arc::new(eq_with('x'))));
+    let err = run(p, &mut TextState::new("ac")).unwrap_err();
+    assert_eq!(err.pos(), 0);
+    assert_eq!(err.unexpected(), Some("'a'"));
+    // a fatal failure of p is not p failing
+    let cut_ab = Arc::new(eq_with::<_, ParseError>('a').then(Arc::new(cut(Arc::new(eq_with('b'))))));
+    let err = not_followed_by(cut_ab).parse(&mut TextState::new("ac")).unwrap_err();
+    assert!(err.is_fatal());
+    assert_eq!(err.pos(), 1);
+    // the error for p matching is built by the error type
+    let digit = SatisfyMap::<char, char, DomainError>::new(String::from("digit"),
+        Arc::new(|c:&char| if c.is_digit(10) { Some(*c) } else { None }));
+    let err = not_followed_by(Arc::new(digit)).parse(&mut TextState::new("1")).unwrap_err();
+    assert_eq!(err.code, Code::Reserved);
+    assert_eq!(err.message(), "'1' is reserved");
+}